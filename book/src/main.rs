@@ -0,0 +1,117 @@
+use game_sdk::GameState;
+use player::book::{Book, BookBuilder, DEFAULT_BOOK_PLIES};
+use player::search::Searcher;
+use player::searchlimits::SearchLimits;
+use std::env;
+
+pub const USAGE: &str = "Usage:
+    book build <out> [-plies N] <fens.txt>...
+    book selfplay <out> [-games N] [-nodes N] [-plies N]
+    book merge <out> <book>...
+    book show <book> [fen]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        println!("{}", USAGE);
+        return;
+    }
+    let out = args[1].as_str();
+    let mut plies = DEFAULT_BOOK_PLIES;
+    let mut games = 100;
+    let mut nodes = 20000;
+    let mut inputs = Vec::new();
+    let mut index = 2;
+    while index < args.len() {
+        match args[index].as_str() {
+            "-plies" => {
+                plies = args[index + 1]
+                    .parse::<u8>()
+                    .expect("Amount of plies given is not a correct number");
+                index += 2;
+            }
+            "-games" => {
+                games = args[index + 1]
+                    .parse::<usize>()
+                    .expect("Amount of games given is not a correct number");
+                index += 2;
+            }
+            "-nodes" => {
+                nodes = args[index + 1]
+                    .parse::<u64>()
+                    .expect("Amount of nodes given is not a correct number");
+                index += 2;
+            }
+            input => {
+                inputs.push(input.to_owned());
+                index += 1;
+            }
+        }
+    }
+    match args[0].as_str() {
+        "build" => {
+            let mut builder = BookBuilder::new(plies);
+            for input in inputs.iter() {
+                let added = builder
+                    .add_fen_log(input)
+                    .unwrap_or_else(|e| panic!("Could not read {}: {}", input, e));
+                println!("Added {} games from {}", added, input);
+            }
+            save(&builder.book, out);
+        }
+        "selfplay" => {
+            let mut builder = BookBuilder::new(plies);
+            let mut searcher = Searcher::deterministic(SearchLimits {
+                nodes: Some(nodes),
+                ..SearchLimits::none()
+            });
+            searcher.listeners.clear();
+            // only the obstacles are random, the deterministic search varies with them and
+            // every ply of the games ends up in the book
+            for game in 0..games {
+                let result = builder.add_selfplay_game(&mut searcher, &GameState::random());
+                println!("Game {} finished, winner {:?}", game + 1, result);
+            }
+            save(&builder.book, out);
+        }
+        "merge" => {
+            let mut book = Book::new();
+            for input in inputs.iter() {
+                book.merge(&load(input));
+            }
+            save(&book, out);
+        }
+        "show" => {
+            let book = load(out);
+            println!("{} positions with {} moves", book.len(), book.move_count());
+            if !inputs.is_empty() {
+                let state = GameState::from_fen(inputs.join(" "));
+                for m in book.moves(&state) {
+                    println!(
+                        "{:<30} weight {:>6} games {:>6} score {:.3}",
+                        m.action.to_string(),
+                        m.weight,
+                        m.games(),
+                        m.score()
+                    );
+                }
+            }
+        }
+        _ => println!("{}", USAGE),
+    }
+}
+
+fn load(path: &str) -> Book {
+    Book::load(path).unwrap_or_else(|e| panic!("Could not load {}: {}", path, e))
+}
+
+fn save(book: &Book, path: &str) {
+    book.save(path)
+        .unwrap_or_else(|e| panic!("Could not save {}: {}", path, e));
+    println!(
+        "Saved {} positions with {} moves to {}",
+        book.len(),
+        book.move_count(),
+        path
+    );
+}
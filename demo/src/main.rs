@@ -1,10 +1,20 @@
 use game_sdk::{Action, ClientListener, Color, GameState};
+use player::evaluation::{evaluate_trace, DEFAULT};
 use player::mcts::MCTS;
 use player::search::Searcher;
 use player::timecontrol::Timecontrol;
+use std::env;
 use std::time::Instant;
 
 fn main() {
+    // `demo eval <fen>` prints the evaluation breakdown of a position
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() > 1 && args[0] == "eval" {
+        let state = GameState::from_fen(args[1..].join(" "));
+        println!("{}", state);
+        println!("{}", evaluate_trace(&state, &DEFAULT));
+        return;
+    }
     // Do some perft testing
     //FEN of midgame position:
     //FEN: 20 RED 19807040637789456435240771584 576460752303423488 0 0 0 140737488355328 0 0 0 34359738368 4835703278458585418301440 140737488355328 2417851639229258349412352 8388608 2361183241434822606848 144115188109410304 0 288230376151711744 576460752303423488
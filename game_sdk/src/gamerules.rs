@@ -353,7 +353,8 @@ pub fn get_beetle_accessible_neighbours(occupied: u128, obstacles: u128, field:
 }
 
 pub fn are_connected_in_swarm(occupied: u128, to_check: u128) -> bool {
-    if to_check.count_ones() == 1 {
+    // a lone piece has no neighbours to check
+    if to_check.count_ones() <= 1 {
         return true;
     }
     let mut visited = 1u128 << to_check.trailing_zeros();
@@ -8,7 +8,6 @@ use crate::gamestate::Color::{BLUE, RED};
 use crate::hashing::{BEETLE_STACK_HASH, COLOR_TO_MOVE_HASH, PIECE_HASH, PLY6_HASH};
 use crate::piece_type::{PieceType, PIECETYPE_VARIANTS};
 use colored::Colorize;
-use rand::prelude::ThreadRng;
 use rand::Rng;
 use std::fmt::{Display, Formatter, Result};
 
@@ -236,6 +235,17 @@ impl GameState {
                 pieces[j][i] = entries.remove(0).parse::<u128>().unwrap();
             }
         }
+        GameState::from_bitboards(ply, color_to_move, obstacles, pieces, beetle_stack)
+    }
+
+    /// State with the given pieces, everything else is derived from them
+    pub fn from_bitboards(
+        ply: u8,
+        color_to_move: Color,
+        obstacles: u128,
+        pieces: [[u128; 2]; 5],
+        beetle_stack: [[u128; 2]; 4],
+    ) -> GameState {
         let hash = GameState::calculate_hash(&pieces, color_to_move, &beetle_stack, ply);
         let mut occupied = [0u128; 2];
         for index in 0..128 {
@@ -336,18 +346,22 @@ impl GameState {
     }
 
     pub fn random() -> GameState {
+        GameState::random_with(&mut rand::thread_rng())
+    }
+
+    /// Start position with obstacles drawn from `rng`
+    pub fn random_with<R: Rng>(rng: &mut R) -> GameState {
         let mut res = GameState::new();
-        let mut rng = rand::thread_rng();
         let mut obstacles = 0u128;
         while obstacles.count_ones() < 3 {
-            obstacles |= GameState::valid_occ_field_bb(&mut rng);
+            obstacles |= GameState::valid_occ_field_bb(rng);
         }
         res.obstacles = obstacles;
         debug_assert!(res.check_integrity());
         res
     }
 
-    fn valid_occ_field_bb(rng: &mut ThreadRng) -> u128 {
+    fn valid_occ_field_bb<R: Rng>(rng: &mut R) -> u128 {
         loop {
             let pos = rng.gen_range(0, 121);
             if (1u128 << pos) & VALID_FIELDS > 0 {
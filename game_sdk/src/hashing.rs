@@ -13,6 +13,18 @@ pub const PIECE_HASH: [[[u64; 121]; 2]; 5] = [[[1585975039535028632u64, 88205852
 pub const BEETLE_STACK_HASH: [[[u64; 121]; 2]; 4] = [[[2868723996869826093u64, 12276068372645813960u64, 10646342060038796623u64, 7263966673172571338u64, 9840460840585336328u64, 15908816695321910135u64, 10086383563664154790u64, 6627519004412918827u64, 11066064928975378194u64, 3901507167437539113u64, 15277456709887205511u64, 6010438724718824457u64, 15483608496151109562u64, 1349491841806618781u64, 15127877054314633130u64, 4813496892132540980u64, 3691359413171169636u64, 9679106988098231732u64, 4119830025109015450u64, 12698124614859591297u64, 12206141884203676932u64, 15414730809524726843u64, 10413735586674746199u64, 15459553180414085448u64, 9216765223259524786u64, 4982267679294089210u64, 6928955508172656421u64, 993351680314309601u64, 5615298996551604858u64, 14832802333413540725u64, 5478323664948780812u64, 14487215809292384564u64, 9438607282277607302u64, 17034485992999758214u64, 12789195134417801762u64, 18437873085777187494u64, 17201196758616498008u64, 15039571263090086706u64, 12165720923065668983u64, 17505939267098866559u64, 15354408840735262676u64, 165298404670143617u64, 12642729348469868179u64, 1487427180648020059u64, 1289670210723381114u64, 4878519017462805605u64, 3528931707782723394u64, 17697095001061239425u64, 5281420513191197325u64, 16485634636082274400u64, 5702135811496910098u64, 7099767250199893160u64, 6979726793757900717u64, 338610246903662898u64, 12175984621300110547u64, 14837403194624104372u64, 5616073346020194154u64, 9738905314923886630u64, 5108272246693879673u64, 17236139589323417372u64, 1512516216246546756u64, 7570148802448542116u64, 16661512376202267046u64, 11216608286927071076u64, 17874896734077149426u64, 18207188763277435062u64, 9801721914583083945u64, 5288396777870828859u64, 6088457811966138627u64, 5360155694582678944u64, 8413132598719247793u64, 6223387693336153664u64, 17652184240204262142u64, 2314396467111270948u64, 4594580210701589311u64, 4017750277493801816u64, 10553534976722363487u64, 10361296019358644137u64, 15690932811012944013u64, 8932615077256001206u64, 18316398640735207905u64, 7740025573936056275u64, 16628499343692394273u64, 9982067915897890578u64, 3015388169003943619u64, 9838006766941259061u64, 5149392584691295062u64, 1515506782459508215u64, 16693105327367316836u64, 12880010902405084828u64, 1786741899826360506u64, 14067332272081662144u64, 10114270095695713981u64, 12558295281814663807u64, 4916739670161593990u64, 4701306060747168418u64, 16047490845231698876u64, 12702197133935084820u64, 1635744584077747692u64, 1231779608304051843u64, 16727896049604128744u64, 5528196341145308086u64, 11981576140700327413u64, 4438214533718472220u64, 1120508737177918848u64, 8960779720530993056u64, 10336897630879346373u64, 12762931407410366027u64, 9310844338831275u64, 16624603835942969040u64, 2349261291983644055u64, 2771625729669160807u64, 11327811930559794762u64, 15225018310762179666u64, 11981471029472079683u64, 11185588857240984199u64, 11639119113135653050u64, 7333749387467621100u64, 13056951599881083197u64, 15062414285885635355u64, 1586511860504278521u64, ], [1031986291858057821u64, 947507981527000793u64, 13086262501483381607u64, 9594471488463439209u64, 15812185130539380369u64, 7483351889524739355u64, 2314806541524657210u64, 1081059971151541321u64, 6339614464475980857u64, 1489314423789938280u64, 17393152900798751544u64, 9225655013013443709u64, 11267073821136497639u64, 16921218923252170550u64, 10945544695843475214u64, 995788069334348409u64, 8730984751781562323u64, 17802879341901509324u64, 8171533772421498820u64, 12352240913643574241u64, 12944868450545737917u64, 16516879242643344381u64, 7240064013944701773u64, 753764716146317358u64, 9141327435480147760u64, 5699270365746134816u64, 16584759585071600354u64, 4440319073312480645u64, 179998598571081865u64, 9838237875385344612u64, 15842186248849261258u64, 11307686819655953422u64, 17584427200191064265u64, 18234512803663938806u64, 2180543975663767398u64, 2520776194168312691u64, 15250835498828862965u64, 10665281393843793997u64, 6834732979028251120u64, 8516755475644892754u64, 10225729232209791338u64, 7801021051897733952u64, 16464268698833213762u64, 411884153004128737u64, 2416966943647301163u64, 2211456554036731541u64, 14495231464932991533u64, 384820904045436186u64, 4274831474367895176u64, 6310832147148117819u64, 8336013296942118430u64, 11756474957880776732u64, 8860791945156682597u64, 2768505312169678591u64, 9027231567885748845u64, 2018827831273543928u64, 12087328042196534298u64, 3034149311581414653u64, 1795890682186208597u64, 12164082895970166530u64, 3965085047797315459u64, 13500656450055283974u64, 9445947995881699585u64, 8982744264248999682u64, 12369283144606793814u64, 9129591612087524578u64, 2234118706750941060u64, 17712985725692284225u64, 10554594887888645240u64, 14529992056539934559u64, 16452655422678802560u64, 3236379709368348793u64, 17987862666406553761u64, 11108334332666002262u64, 7605888472366400975u64, 11277928960533092313u64, 3555147619487174258u64, 5858241328985809376u64, 11977811818523610626u64, 9677648292754478974u64, 3041513408263364568u64, 3872971835599563577u64, 12808769205089897871u64, 2216309427613930173u64, 9704410883857072562u64, 13300712430762413085u64, 17712645669258933541u64, 2389551049624283332u64, 8324650025735593867u64, 16209537678277911860u64, 13699143318899679695u64, 12716024674497632910u64, 7486591850632829069u64, 12797883748309365512u64, 13110385467877089802u64, 672617595132326754u64, 14023349079723875645u64, 6668562921758308468u64, 861912325262554222u64, 9796042152470613456u64, 2683661476446488349u64, 13286067742934210786u64, 10789185123401327129u64, 9039705036597280004u64, 17507029704613386702u64, 17945556321355217621u64, 9097376339319292854u64, 14473826168157862854u64, 14065697814703587730u64, 7069703850220461763u64, 15418385922275367047u64, 17395741056372136307u64, 15730197489010755381u64, 4827932063012048427u64, 17363336546485357045u64, 8824502256891814758u64, 16451158572520188624u64, 3928509097509451369u64, 8705810821793694430u64, 1570308855253870037u64, 7925964668562920933u64, ], ], [[5651001735777426754u64, 15904209497462944187u64, 8115496505911408923u64, 3539937059858746286u64, 2115127024852892650u64, 6923651525312426484u64, 10847708281108548051u64, 1557396139846570780u64, 7236380286717077298u64, 16177592420575502170u64, 9192045157560711246u64, 4245375161714209088u64, 7773043429006343034u64, 6152907513653479682u64, 9349711429934803020u64, 9091858596420519832u64, 8464743621247493343u64, 3592796251680915751u64, 5028903032466089256u64, 13721003418908704022u64, 13763546917930504754u64, 17107619106481802199u64, 2295052705325922880u64, 12251873689671632443u64, 10369415824580775874u64, 4572851441652999038u64, 12704050113592009962u64, 2675194347779240323u64, 9332675932341947525u64, 13110090333466331674u64, 9246882785076266683u64, 15465728404213206355u64, 3240012450505798741u64, 11919191647819337947u64, 68843752006665127u64, 12355890770753276721u64, 15954425358170560767u64, 4453051609510609115u64, 5593799680498915161u64, 1772071553955878880u64, 17088047675441707155u64, 9825337781415324037u64, 17599590676342810457u64, 14424666157333908962u64, 12044364857519818927u64, 9549367026427732169u64, 6512906138467555482u64, 12894553955575449121u64, 15483079928134649117u64, 9305826052662031061u64, 13018492356426378840u64, 17980609547196377963u64, 1221752223949193114u64, 14787189814383576262u64, 2624298298596039747u64, 10615635393607254144u64, 13927059675032388276u64, 14614403844358971694u64, 13837329335569528661u64, 18264911308144496687u64, 10956629295489398317u64, 10072533559570052929u64, 16335261605508264047u64, 8267372759598978900u64, 11896538819574995914u64, 16900234584553642724u64, 11492584336010109659u64, 11315408408907112425u64, 8430981937055507591u64, 3965094776380383650u64, 16203902375141809070u64, 9076437228747109722u64, 14778477144055922797u64, 15257348625361851383u64, 13339003806294699533u64, 5933811264615686088u64, 8761788093393998284u64, 13731153878837498522u64, 17007761054007452489u64, 16935402345849352300u64, 9665377197443899712u64, 13016259143270954606u64, 7904847691350701170u64, 4755860250328979875u64, 4015509320937150283u64, 11381778265275067762u64, 12776707454443992702u64, 9777686255436582209u64, 9635207436500427656u64, 1903944209422240666u64, 16218326578109242692u64, 18115071709444608123u64, 10831413264118837152u64, 3605302903205421046u64, 7726170801857838581u64, 6828292677258863731u64, 9342792032440173585u64, 4863083987009351898u64, 642600724899843863u64, 5807744685732416204u64, 5895291728564010121u64, 16243580231858776731u64, 256071256494161513u64, 9582254993033346488u64, 8607731076626084314u64, 7250179993115067470u64, 4875848067558676398u64, 14578897618582767202u64, 11416114588125139763u64, 2496698147874238164u64, 2681079197432113975u64, 8167178313984891368u64, 17469838360104288869u64, 13536001236171826458u64, 14382878988570041867u64, 11809483383132692508u64, 11923345711270562720u64, 4573498377325350791u64, 8263911768719651165u64, 5838012380763311290u64, 606408498297066606u64, ], [5487273314606921162u64, 100098396967611637u64, 863697874410403801u64, 6011382612864677724u64, 15356632780259288326u64, 14956729201610788769u64, 9831991840499973366u64, 12959154980777961147u64, 5858003329070497743u64, 14694874091324427063u64, 17129307051553520590u64, 6858947380917915185u64, 8326411326047265514u64, 7772941886685914943u64, 12928430200467189028u64, 16913793853499229190u64, 6693935199176105285u64, 9470663762700621572u64, 14256539847100636694u64, 15270056465859175246u64, 11919973253944799795u64, 17034313739125447713u64, 11353164599248491718u64, 859273253577775968u64, 2533095705658998361u64, 15544137245009733487u64, 9133958916072031878u64, 15381703592055421792u64, 3231489733782648250u64, 3667061465627767226u64, 14314155300250506642u64, 8575699627076011743u64, 315991662141566770u64, 5409355172421446206u64, 17347716447245966184u64, 15151190342362219861u64, 2504839488677759672u64, 3304656173639839991u64, 10507963687550068695u64, 11512682245762328703u64, 1854316337770236038u64, 702424554898712671u64, 1016125723113046989u64, 4719720568530258196u64, 422830875030851304u64, 12279383791536290064u64, 2521254955203863838u64, 4892703933426570378u64, 17139251953052855021u64, 1990959657694522080u64, 17040374584200024048u64, 7133050907129534077u64, 5586111311779010854u64, 6076983930471926794u64, 12734077722017328378u64, 8227983307845333571u64, 16268733042117403559u64, 10788721237726565322u64, 4303328980175923944u64, 8238452194511645982u64, 9404808980758335653u64, 3840057601111065740u64, 10957130966514168622u64, 16470716391440687249u64, 14630624618438014187u64, 12400536149830405894u64, 14388600526102321814u64, 17555423247413956442u64, 8640658714766669485u64, 14065522670954387756u64, 11376154205651739965u64, 3551089700537900339u64, 7129811008301237291u64, 1568083084205939306u64, 13864134608533296857u64, 8578656362973351854u64, 3090852370314344632u64, 10307258542658372822u64, 15376663429050701977u64, 6637285914898466364u64, 14141376808481599501u64, 8807811503075111476u64, 1897002191011771331u64, 13868323387634192917u64, 11943759780553536274u64, 17753350186842777087u64, 10758366837119487297u64, 17195092093081475115u64, 10529202191956200660u64, 13507411613456197830u64, 2366810927821149635u64, 2752782315010983679u64, 2806389743851890651u64, 8035522450033624513u64, 5315276807797792162u64, 11635308591438503201u64, 6374673625152807399u64, 17740123859070428351u64, 5727412664399686834u64, 5807209591934668666u64, 15384439508117035889u64, 16114313377185493837u64, 3028940238246936567u64, 4996321453737967505u64, 11469705476541163289u64, 12294513434689800509u64, 16853472758963303701u64, 383044131312325460u64, 15337064524551685199u64, 15943697747027296029u64, 3735922638353913161u64, 11049067347974250486u64, 13833077149525648963u64, 7008105817371025991u64, 55789336882295320u64, 3798307113267621054u64, 1786805280971411395u64, 10972632046264561937u64, 15375740950694259246u64, 6804350935790796102u64, 1455773071752236797u64, ], ], [[7027508724463308698u64, 739062254878588218u64, 11996619264848437708u64, 10353392680356980864u64, 13072193858560561425u64, 13205794506590915557u64, 11837354493391427814u64, 4338691403339366189u64, 6196404545848193080u64, 9789329465018111732u64, 3699434866275206467u64, 10280180065814908378u64, 16277147456520008484u64, 1518657276962113760u64, 8838855321607307085u64, 12458559300018803113u64, 8739739318370598894u64, 2362455763589820580u64, 15795713059350666322u64, 12930099483175006167u64, 4953904039215708123u64, 6273827723428532543u64, 6874229158778399291u64, 7695897744678781119u64, 15779987395041569321u64, 10835156713915652896u64, 8779942249183963891u64, 16070674774947776882u64, 10354436313848709918u64, 11560697487037608345u64, 18201180501425643845u64, 3240803419082804203u64, 11821080982416267090u64, 14443902573802267093u64, 2467468749245501521u64, 15284690474547090275u64, 12364696249123449627u64, 8095960611183285946u64, 11755936863504161562u64, 700635627971574440u64, 9598119580582482762u64, 12040173879526755804u64, 9258402485516716962u64, 16968409379441941250u64, 15129883868582097535u64, 13551720303791334005u64, 9325309525408320323u64, 11714331422378877095u64, 17001320812932217877u64, 18301093936638914075u64, 9552780552294502387u64, 471558069624188131u64, 15136572989638544561u64, 6433442098114056706u64, 12279900239498269132u64, 13577500865811395608u64, 9231198709618725015u64, 5305864306209929305u64, 10189154067169615206u64, 15775019909754596073u64, 2788816322776221973u64, 14697034669049971008u64, 4610897390878542933u64, 4822687175087719333u64, 1661241273563673943u64, 17789416174347223249u64, 9388732193336239506u64, 7021790380569480252u64, 9977951450906577923u64, 9063898753523446449u64, 15990142397377940935u64, 1222432329739261519u64, 15560987418210145187u64, 3998986401002536515u64, 11131935511315383917u64, 9390844440703451540u64, 2900136583299738472u64, 7961021282111489882u64, 12528353952870167237u64, 6615453639712553892u64, 13920683114223528989u64, 15335289751798094235u64, 17708533087052764815u64, 6748396479668350654u64, 250964134677802904u64, 14677127571484395063u64, 10303214962574610222u64, 10604291481821699297u64, 4858784804672456803u64, 3499978832026447267u64, 18016169992343311970u64, 10422047772327981517u64, 13463968695910837590u64, 11823656770976194227u64, 8670716645172088552u64, 17394175772646005504u64, 10520367968173708264u64, 1443282420309120272u64, 18434287600938912090u64, 14380001010548962431u64, 4256959963280128608u64, 2264996613909258344u64, 9560201793199437208u64, 2523680988851153332u64, 14843303898218522304u64, 2134098875634181047u64, 827307274843768282u64, 370364696781259173u64, 14432288912780725498u64, 14809472151012843462u64, 9771423463397914007u64, 8767154012294603590u64, 2179510648712464160u64, 10162705683563902974u64, 5688347341875506683u64, 5372915869488422441u64, 10488827682144810774u64, 17318584742552127839u64, 2026712401912175175u64, 11862612870358989315u64, 4611848820094416303u64, ], [10846186633516784578u64, 9492805266166705034u64, 17984947710893628022u64, 9318648189027436773u64, 817407187127078149u64, 8063448764198509816u64, 3634779934301406062u64, 4859791000287670462u64, 1571812946165269808u64, 7554708669701249248u64, 8687066631112822808u64, 11298472288236602027u64, 493619220935614120u64, 10517927312355971450u64, 13474691460225056299u64, 820115243133626873u64, 14617874410859319108u64, 10903766474796735598u64, 6848253360111854338u64, 17454106634804139958u64, 5056156045698347545u64, 1510097582178978560u64, 17974916625212713802u64, 4788395563183865346u64, 16419543978807732363u64, 8322684593531605628u64, 8581645019595760568u64, 10247925745493619835u64, 68395132041103535u64, 11784022259488740819u64, 17988432586818439960u64, 14468953411416583311u64, 13895170598079158189u64, 15818701424192743337u64, 11655344518437810893u64, 13408306866748670576u64, 9228094441391922523u64, 17031745422623146104u64, 17718597578969452830u64, 831485788536777240u64, 889581624204918204u64, 17188587119174039288u64, 11615250000612703325u64, 6688542245806772102u64, 10468075303852221288u64, 18335079371258985497u64, 13000351880011200882u64, 10038502756556278721u64, 4683473059580332741u64, 18422139668868597728u64, 2964677059733484997u64, 7516557932859414762u64, 16633537965558566507u64, 17059058825065431522u64, 11122414729640827546u64, 6242854780896345294u64, 2835304017121530429u64, 11701476240987973188u64, 10332906177843517463u64, 549794718720260388u64, 15488192487791963140u64, 10670853304993715180u64, 17014368241885402453u64, 5016751162994826414u64, 10521238532000573467u64, 5094549508512989546u64, 6942270967297149313u64, 11491188836105898884u64, 2110262312393854974u64, 15156878869676807162u64, 18359933575070052463u64, 3037164916173998344u64, 6241725424823954232u64, 8872756885877449144u64, 14977207832265408u64, 10996436022722274498u64, 14058250564555674825u64, 6935928724985708216u64, 5534626790888181778u64, 9177136053190388237u64, 14727044259619603144u64, 18239123946686591158u64, 242497610265717751u64, 7713683317107820972u64, 5315522176764950652u64, 15590929110306559886u64, 9302697272354921981u64, 2306777418223811311u64, 13432934712218083727u64, 16868757327682705827u64, 8283654838720255834u64, 1800626569368686715u64, 5256770607706339200u64, 4904436068383527141u64, 10394820663870884505u64, 10076969674141737672u64, 4810169853915679950u64, 5376627371623103178u64, 5667908659281777904u64, 10074704454319483037u64, 11711358292926272166u64, 5268728884697968980u64, 15558063549524136582u64, 12289797463330965925u64, 10826741085942158229u64, 12637574059638979490u64, 17366955678396377640u64, 3043764139112943178u64, 17496972845629564103u64, 1603660454946267906u64, 13467795024835327950u64, 12305417466308682194u64, 3112044049966399816u64, 11625765788511523902u64, 17698324709232855610u64, 17209988013749696258u64, 2947406143778354151u64, 12680441983450037427u64, 16578649033375083907u64, 14667647915414116973u64, 15610532123524325002u64, ], ], [[11365171091905026930u64, 5055925531670073463u64, 3891637291574761160u64, 16482388671525801925u64, 9571211899382440625u64, 4784556548330638545u64, 13166633884413963735u64, 7798128986459221014u64, 4313139927145730770u64, 8159046614813801353u64, 1123715541934309991u64, 13492167885309172451u64, 18386577891811773918u64, 33550915247183712u64, 11646534847432489707u64, 16425629492389076129u64, 5665176543265895466u64, 11199795027610103904u64, 3072486400676619736u64, 7265890669621405385u64, 18059668819023971916u64, 292375086768810640u64, 11423056874593920923u64, 1510023731730475255u64, 9201461146900484135u64, 6169460404139918076u64, 15958760973029244451u64, 2595753847282805490u64, 12196366285333405173u64, 11335999739898906727u64, 14516795632473210947u64, 8771305767677249123u64, 13097951915718866519u64, 14119964581571268148u64, 8376308452152323000u64, 15485407239094019626u64, 5064384825498680785u64, 17355366176048935395u64, 7306596676646389202u64, 6806263443989823230u64, 17690427128582547564u64, 15654455826967907984u64, 17919828144957066766u64, 5382965749159835907u64, 4025277478012574348u64, 8341714838121599353u64, 5260749611946658937u64, 12474926777819096227u64, 10895470908797031798u64, 12008045196659159025u64, 12849217579392564646u64, 12911371890296814491u64, 7787194884808617639u64, 6898078190781789562u64, 13630484104379765967u64, 6565908198373279980u64, 6794566174377395446u64, 1981875528104380249u64, 5579725813987110753u64, 15341863826911938713u64, 11141208334464290393u64, 1498299506261172847u64, 15569333562729969568u64, 7614174044162774809u64, 15149565579679701117u64, 13252194176490114344u64, 17323620559772364462u64, 139394904847892197u64, 15997510217805804321u64, 18101136410745535013u64, 13782734342491515179u64, 7710175129408101627u64, 5739003750000802093u64, 12465274655861819931u64, 5872498038481969620u64, 13062951308128700526u64, 8925646367547121226u64, 18365768850124951186u64, 17133770461412855131u64, 2214119826989903712u64, 9640456029591662193u64, 5584920741444668508u64, 12127091657088888014u64, 935517763243998398u64, 74688370805805524u64, 12076378930479948285u64, 15554279497672890786u64, 16490935927407734988u64, 1125466634815532618u64, 11826226609161442891u64, 15792912148914550252u64, 4542302611430578314u64, 7707727180633641384u64, 15138868957240461551u64, 14307260224657667162u64, 8001396343584750564u64, 16091551441594535106u64, 657077194120662905u64, 13176362113779083081u64, 3974633635248272278u64, 15010633874611843984u64, 1134518212628537004u64, 6560719691150951476u64, 10637292015470904070u64, 1157227144180702151u64, 17434772196866037434u64, 5769768760784364244u64, 13575807305216355313u64, 14866330747590973981u64, 8498389565197918144u64, 8370555050683989467u64, 18154435794304540976u64, 17184146919705508291u64, 3733582859988081390u64, 15501025014097858356u64, 8867601909438764917u64, 13965838077812760684u64, 14378584669408646761u64, 3327213969449234733u64, 2564727813796336911u64, 8991610377288112153u64, ], [13135040222306996055u64, 3863275412766830292u64, 12523488872907491930u64, 2449157198587216339u64, 5637117293446890154u64, 13671805841183866356u64, 15040466050137225380u64, 9327709222705666900u64, 4110982974297812249u64, 8866754667382651353u64, 16340129529251077466u64, 16893938550367207269u64, 11519419919382540370u64, 17355796268774735258u64, 3453071879478385230u64, 11444925706791328432u64, 17841031939337705610u64, 1466575791524790424u64, 3611208575494529211u64, 2863439490917463606u64, 9351474011715170770u64, 8831021279544929816u64, 8391978529459228734u64, 885296987492706330u64, 14320101138698732187u64, 15352124495101586375u64, 10737298708994349009u64, 7264601624882964173u64, 890391696897477523u64, 4975809754430174104u64, 7732674306663796075u64, 7261998004383482983u64, 15094627371587227849u64, 11442819251972564295u64, 3122744637700964155u64, 8197170548056245817u64, 2254916005062345386u64, 15863923909012675277u64, 4381772290205399376u64, 765185889147777578u64, 3457227026403052680u64, 9467755883957752416u64, 3049059831073363615u64, 7688519772401108336u64, 9872502957206332457u64, 5061886317158958399u64, 8038769492352536387u64, 4056096726106913204u64, 10917686019365734428u64, 1380140867211544366u64, 16247307694411683374u64, 4001134421620932711u64, 12572459428242287718u64, 12636362272659562126u64, 8076215909974435982u64, 17991098146597773010u64, 10130921740114930057u64, 7449492107013772738u64, 17491711968708111929u64, 10065741758683734078u64, 15088301206363536163u64, 17090542570690739663u64, 8035683864305150848u64, 3725471982599240076u64, 13400169832783912985u64, 13110614321763442228u64, 11483179098793669011u64, 366257092822862817u64, 14653414931629296009u64, 9114385647927349257u64, 10620792989062186131u64, 2282089613210725564u64, 6312797899174462793u64, 6731723818091444218u64, 1396990909440986599u64, 7397125681000825042u64, 5144832595883745650u64, 16423014133297113430u64, 4618169754023694770u64, 12039133027795965384u64, 9569065713684255995u64, 6267455484603623131u64, 2664527686784734709u64, 8656511651318224195u64, 332446820909115121u64, 6077173893997118483u64, 16947394432188492805u64, 3444925502625291499u64, 4078241802707936137u64, 5900603628365288123u64, 15875395218889316549u64, 17316178861759347473u64, 4729478888053889456u64, 16269479219673081448u64, 14963215885884468265u64, 8374273700443275844u64, 9232356886520855301u64, 3533144442849189675u64, 7250658075792595079u64, 1547616628345233276u64, 1723524275363298620u64, 6960457704800555710u64, 4286798477887260549u64, 16761157021515947596u64, 16363164043712164233u64, 11031003385461646393u64, 2508418511656480339u64, 16573760358351695103u64, 12638322635859784719u64, 16966356042698168587u64, 827538381316637960u64, 7532661912940229470u64, 5315280353005801742u64, 18072791731873405605u64, 6361417649895790865u64, 5932563711298666601u64, 14058504217106453549u64, 3498357634804866097u64, 11051442857266463679u64, 9464755968296783777u64, 4474311750696898390u64, ], ], ];
 pub const COLOR_TO_MOVE_HASH: u64 = 8703267711273452815;
 pub const PLY6_HASH: u64 = 8019904076704556913;
+
+/// Fingerprint of all Zobrist keys above. Data that stores hashes, like learning files,
+/// is only valid as long as the keys do not change
+pub fn zobrist_fingerprint() -> u64 {
+    let keys = PIECE_HASH
+        .iter()
+        .flat_map(|colors| colors.iter())
+        .chain(BEETLE_STACK_HASH.iter().flat_map(|colors| colors.iter()))
+        .flat_map(|squares| squares.iter())
+        .chain([COLOR_TO_MOVE_HASH, PLY6_HASH].iter());
+    keys.fold(0u64, |res, key| res.rotate_left(7) ^ key)
+}
 impl HashKeys {
     pub fn gen_hash_keys() {
         let keys = HashKeys::from_seed([42; 32]);
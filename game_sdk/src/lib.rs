@@ -9,11 +9,13 @@ pub(crate) mod hashing;
 pub mod misc;
 pub mod neighbor_magic;
 mod piece_type;
+pub mod searchinfo;
+pub mod symmetry;
 pub use action::Action;
 pub use actionlist::ActionList;
 pub use gamestate::Color;
 pub use gamestate::GameState;
-pub use hashing::HashKeys;
+pub use hashing::{zobrist_fingerprint, HashKeys};
 pub use neighbor_magic::get_accessible_neighbors;
 pub use piece_type::PieceType;
 
@@ -43,6 +45,10 @@ mod tests {
 /// Trait that needs to be implemented for every Player
 /// The "on"-methods are called on the events
 pub trait ClientListener {
+    /// Called once with the first state of a game, before any other state update.
+    /// The obstacles are known from here on, so per game data can be prepared
+    fn on_game_start(&mut self, _state: &GameState) {}
+
     /// This function is called whenever a memento message is received from the
     /// server. It is given a gamestate-struct
     fn on_update_state(&mut self, _state: &GameState) {}
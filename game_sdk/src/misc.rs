@@ -65,7 +65,11 @@ impl Iterator for GameReader {
                 result = get_result(&state);
                 break;
             }
+            // the score may be followed by its type
             let search_res = desc
+                .split_whitespace()
+                .next()
+                .unwrap_or("")
                 .replace("Some(", "")
                 .replace(")", "")
                 .parse::<i16>()
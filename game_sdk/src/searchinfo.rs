@@ -0,0 +1,214 @@
+use crate::{Action, ActionList, PieceType, MATE_IN_MAX};
+use std::fmt::{Display, Formatter, Result};
+
+/// Unit of the score of a search
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScoreType {
+    /// Units of the static evaluation
+    Evaluation,
+    /// Win rate in percent
+    WinRate,
+}
+
+impl ScoreType {
+    pub fn token(self) -> &'static str {
+        match self {
+            ScoreType::Evaluation => "eval",
+            ScoreType::WinRate => "winrate",
+        }
+    }
+
+    pub fn from_token(token: &str) -> Option<ScoreType> {
+        match token {
+            "eval" => Some(ScoreType::Evaluation),
+            "winrate" => Some(ScoreType::WinRate),
+            _ => None,
+        }
+    }
+}
+
+/// Snapshot of a search, emitted after every completed iteration
+#[derive(Clone, Debug, Default)]
+pub struct SearchInfo {
+    pub depth: usize,
+    pub seldepth: usize,
+    /// Score from the perspective of the side to move at the root
+    pub score: i16,
+    /// None if the engine did not tell, e.g. the info was parsed from an older engine
+    pub score_type: Option<ScoreType>,
+    /// Plies until the game is won (positive) or lost (negative), if the score is a terminal score
+    pub mate: Option<i16>,
+    pub nodes: u64,
+    pub nps: f64,
+    /// Elapsed time in milliseconds
+    pub time: u64,
+    /// Permill of the transposition table in use, None for searches without one
+    pub hashfull: Option<usize>,
+    pub pv: Vec<Action>,
+}
+
+impl SearchInfo {
+    pub fn mate_from_score(score: i16) -> Option<i16> {
+        if score.abs() >= MATE_IN_MAX {
+            Some((MATE_IN_MAX + 60 - score.abs()) * score.signum())
+        } else {
+            None
+        }
+    }
+
+    pub fn pv_from_actionlist(pv: &ActionList<Action>) -> Vec<Action> {
+        (0..pv.size).map(|i| pv[i]).collect()
+    }
+
+    pub fn best_action(&self) -> Option<Action> {
+        self.pv.first().copied()
+    }
+
+    /// Parses a line as printed by `Display`. The principal variation is not parsed.
+    /// Lines without a depth, such as periodic nps output, are ignored
+    pub fn parse(line: &str) -> Option<SearchInfo> {
+        let args = line.split_whitespace().collect::<Vec<&str>>();
+        if args.first() != Some(&"info") {
+            return None;
+        }
+        let mut res = SearchInfo::default();
+        let mut has_depth = false;
+        let mut index = 1;
+        while index + 1 < args.len() {
+            let value = args[index + 1];
+            match args[index] {
+                "depth" => {
+                    res.depth = value.parse().ok()?;
+                    has_depth = true;
+                }
+                "seldepth" => res.seldepth = value.parse().ok()?,
+                "score" => res.score = value.parse().ok()?,
+                "scoretype" => res.score_type = ScoreType::from_token(value),
+                "mate" => res.mate = Some(value.parse().ok()?),
+                "nodes" => res.nodes = value.parse().ok()?,
+                "nps" => res.nps = value.parse().ok()?,
+                "time" => res.time = value.parse().ok()?,
+                "hashfull" => res.hashfull = Some(value.parse().ok()?),
+                "pv" => break,
+                _ => {
+                    index += 1;
+                    continue;
+                }
+            }
+            index += 2;
+        }
+        if has_depth {
+            Some(res)
+        } else {
+            None
+        }
+    }
+}
+
+/// Rate of a periodic `info nps` line, which is sent during long iterations and has no depth
+pub fn parse_nps(line: &str) -> Option<f64> {
+    match line.split_whitespace().collect::<Vec<&str>>()[..] {
+        ["info", "nps", nps] => nps.parse().ok(),
+        _ => None,
+    }
+}
+
+impl Display for SearchInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "info depth {} seldepth {} score {}",
+            self.depth, self.seldepth, self.score
+        )?;
+        if let Some(score_type) = self.score_type {
+            write!(f, " scoretype {}", score_type.token())?;
+        }
+        if let Some(mate) = self.mate {
+            write!(f, " mate {}", mate)?;
+        }
+        if let Some(best_action) = self.best_action() {
+            write!(f, " bestmove {:?}", best_action)?;
+        }
+        write!(
+            f,
+            " nodes {} nps {:.2} time {}",
+            self.nodes, self.nps, self.time
+        )?;
+        if let Some(hashfull) = self.hashfull {
+            write!(f, " hashfull {}", hashfull)?;
+        }
+        write!(f, " pv {}", format_pv(&self.pv))
+    }
+}
+
+pub fn format_pv(pv: &[Action]) -> String {
+    let mut ret = "".to_owned();
+
+    for i in 0..pv.len() {
+        match pv[i] {
+            Action::SkipMove => ret.push_str("Skip"),
+            Action::SetMove(piece_type, to) => {
+                let mut part = "Set(".to_owned();
+                part.push_str(&format_piecetype_pv(piece_type));
+                part.push_str(&format!(", {})", to));
+                ret.push_str(&part);
+            }
+            Action::DragMove(piece_type, from, to) => {
+                let mut part = "Drag(".to_owned();
+                part.push_str(&format_piecetype_pv(piece_type));
+                part.push_str(&format!(", {}, {})", from, to));
+                ret.push_str(&part);
+            }
+        }
+        if i != pv.len() - 1 {
+            ret.push(' ');
+        }
+    }
+
+    ret
+}
+
+fn format_piecetype_pv(piece_type: PieceType) -> String {
+    match piece_type {
+        PieceType::BEE => "Q",
+        PieceType::BEETLE => "B",
+        PieceType::ANT => "A",
+        PieceType::GRASSHOPPER => "G",
+        PieceType::SPIDER => "S",
+    }
+    .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_nps, ScoreType, SearchInfo};
+    use crate::{Action, PieceType, MATE_IN_MAX};
+
+    #[test]
+    fn info_line_roundtrip() {
+        let info = SearchInfo {
+            depth: 7,
+            seldepth: 9,
+            score: MATE_IN_MAX + 55,
+            score_type: Some(ScoreType::Evaluation),
+            mate: SearchInfo::mate_from_score(MATE_IN_MAX + 55),
+            nodes: 123456,
+            nps: 1000.5,
+            time: 123,
+            hashfull: Some(17),
+            pv: vec![Action::SetMove(PieceType::ANT, 22), Action::SkipMove],
+        };
+        let parsed = SearchInfo::parse(&format!("{}", info)).unwrap();
+        assert_eq!(parsed.depth, 7);
+        assert_eq!(parsed.seldepth, 9);
+        assert_eq!(parsed.score, MATE_IN_MAX + 55);
+        assert_eq!(parsed.score_type, Some(ScoreType::Evaluation));
+        assert_eq!(parsed.mate, Some(5));
+        assert_eq!(parsed.nodes, 123456);
+        assert_eq!(parsed.time, 123);
+        assert_eq!(parsed.hashfull, Some(17));
+        assert!(SearchInfo::parse("info nps 1000").is_none());
+        assert_eq!(parse_nps("info nps 1000.5"), Some(1000.5));
+        assert_eq!(parse_nps(&format!("{}", info)), None);
+    }
+}
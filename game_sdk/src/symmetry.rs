@@ -0,0 +1,141 @@
+use super::action::Action;
+use super::gamestate::GameState;
+use crate::bitboard::constants::VALID_FIELDS;
+
+/// The hexagonal board can be rotated by multiples of 60 degrees and mirrored
+pub const NUM_SYMMETRIES: usize = 12;
+
+/// Maps `square` with symmetry `sym`. Symmetries 6 to 11 mirror the board at the
+/// diagonal through the center first, then all of them rotate by `sym % 6` * 60 degrees
+pub fn transform_square(sym: usize, square: u8) -> u8 {
+    debug_assert!(sym < NUM_SYMMETRIES);
+    let (mut q, mut r) = ((square % 11) as i8 - 5, (square / 11) as i8 - 5);
+    if sym >= 6 {
+        std::mem::swap(&mut q, &mut r);
+    }
+    for _ in 0..sym % 6 {
+        // the neighbour directions (1, 0), (1, 1), (0, 1), ... are rotated into the next one
+        let rotated = (q - r, q);
+        q = rotated.0;
+        r = rotated.1;
+    }
+    ((r + 5) * 11 + q + 5) as u8
+}
+
+pub fn transform_bitboard(sym: usize, mut bitboard: u128) -> u128 {
+    debug_assert_eq!(bitboard & !VALID_FIELDS, 0);
+    let mut res = 0u128;
+    while bitboard > 0 {
+        let square = bitboard.trailing_zeros() as u8;
+        bitboard ^= 1u128 << square;
+        res |= 1u128 << transform_square(sym, square);
+    }
+    res
+}
+
+pub fn transform_action(sym: usize, action: Action) -> Action {
+    match action {
+        Action::SkipMove => Action::SkipMove,
+        Action::SetMove(piece_type, to) => Action::SetMove(piece_type, transform_square(sym, to)),
+        Action::DragMove(piece_type, from, to) => Action::DragMove(
+            piece_type,
+            transform_square(sym, from),
+            transform_square(sym, to),
+        ),
+    }
+}
+
+/// Symmetry that undoes `sym`
+pub fn inverse_symmetry(sym: usize) -> usize {
+    // mirroring is its own inverse, after it the rotation goes the other way
+    if sym >= 6 {
+        sym
+    } else {
+        (6 - sym) % 6
+    }
+}
+
+/// Hash of the obstacles, which are not part of `GameState::hash`
+pub fn obstacle_hash(obstacles: u128) -> u64 {
+    let mix = |mut x: u64| {
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        x ^ (x >> 31)
+    };
+    mix(obstacles as u64 ^ mix((obstacles >> 64) as u64))
+}
+
+impl GameState {
+    pub fn transformed(&self, sym: usize) -> GameState {
+        let mut res = self.clone();
+        for color in 0..2 {
+            res.occupied[color] = transform_bitboard(sym, self.occupied[color]);
+            for piece_type in 0..5 {
+                res.pieces[piece_type][color] =
+                    transform_bitboard(sym, self.pieces[piece_type][color]);
+            }
+            for index in 0..4 {
+                res.beetle_stack[index][color] =
+                    transform_bitboard(sym, self.beetle_stack[index][color]);
+            }
+        }
+        res.obstacles = transform_bitboard(sym, self.obstacles);
+        res.hash =
+            GameState::calculate_hash(&res.pieces, res.color_to_move, &res.beetle_stack, res.ply);
+        res
+    }
+
+    /// Symmetry that maps this position to the representative of all its symmetric
+    /// positions, including the obstacles
+    pub fn canonical_symmetry(&self) -> usize {
+        (0..NUM_SYMMETRIES)
+            .min_by_key(|&sym| {
+                let state = self.transformed(sym);
+                state.hash ^ obstacle_hash(state.obstacles)
+            })
+            .unwrap()
+    }
+
+    /// Hash that is equal for all symmetric positions
+    pub fn canonical_hash(&self) -> u64 {
+        let state = self.transformed(self.canonical_symmetry());
+        state.hash ^ obstacle_hash(state.obstacles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inverse_symmetry, transform_action, transform_square, NUM_SYMMETRIES};
+    use crate::gamerules::calculate_legal_moves;
+    use crate::{ActionList, GameState};
+
+    #[test]
+    fn symmetric_positions_have_symmetric_moves() {
+        let mut state = GameState::new();
+        state.obstacles = (1u128 << 5) | (1u128 << 49) | (1u128 << 107);
+        let mut al = ActionList::default();
+        for _ in 0..8 {
+            calculate_legal_moves(&state, &mut al);
+            state.make_action(al[al.size / 2]);
+        }
+        calculate_legal_moves(&state, &mut al);
+        let canonical_hash = state.canonical_hash();
+        for sym in 0..NUM_SYMMETRIES {
+            assert_eq!(
+                transform_square(inverse_symmetry(sym), transform_square(sym, 17)),
+                17
+            );
+            let transformed = state.transformed(sym);
+            assert!(transformed.check_integrity());
+            assert_eq!(transformed.canonical_hash(), canonical_hash);
+            let mut transformed_al = ActionList::default();
+            calculate_legal_moves(&transformed, &mut transformed_al);
+            assert_eq!(transformed_al.size, al.size);
+            for i in 0..al.size {
+                assert!(transformed_al
+                    .find_action(transform_action(sym, al[i]))
+                    .is_some());
+            }
+        }
+    }
+}
@@ -0,0 +1,377 @@
+use crate::search::Searcher;
+use game_sdk::gamerules::{calculate_legal_moves, get_result, is_game_finished};
+use game_sdk::misc::FenReader;
+use game_sdk::symmetry::{inverse_symmetry, obstacle_hash, transform_action};
+use game_sdk::{zobrist_fingerprint, Action, ActionList, Color, GameState};
+use rand::Rng;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::path::Path;
+
+pub const BOOK_HEADER: &str = "#hivebook";
+/// Has to be increased whenever the file layout or the canonical hash changes
+pub const BOOK_VERSION: u32 = 1;
+/// Positions up to this ply are added to a book by default
+pub const DEFAULT_BOOK_PLIES: u8 = 8;
+/// A move has to be played at least this often before it is played from the book
+pub const MIN_BOOK_GAMES: u32 = 3;
+/// Moves that scored worse than this are never played from the book
+pub const MIN_BOOK_SCORE: f64 = 0.4;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BookMove {
+    pub action: Action,
+    /// Relative probability of playing the move
+    pub weight: u32,
+    /// Results of the games the move was played in, from the view of the player that made it
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+impl BookMove {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn score(&self) -> f64 {
+        if self.games() == 0 {
+            0.5
+        } else {
+            (self.wins as f64 + self.draws as f64 / 2.) / self.games() as f64
+        }
+    }
+
+    fn add(&mut self, other: &BookMove) {
+        self.weight += other.weight;
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+    }
+}
+
+/// Move statistics of early positions. Symmetric positions share one entry, which
+/// is keyed by `GameState::canonical_hash` and stores the moves in the canonical orientation
+#[derive(Default)]
+pub struct Book {
+    positions: HashMap<u64, Vec<BookMove>>,
+}
+
+impl Book {
+    pub fn new() -> Book {
+        Book::default()
+    }
+
+    /// Reads a book written by `save`. Books of another version or with other hash keys are an error
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Book> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header = lines.next().unwrap_or_else(|| Ok(String::new()))?;
+        let expected = format!("{} {} {}", BOOK_HEADER, BOOK_VERSION, zobrist_fingerprint());
+        if header.trim_end() != expected {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("book header is '{}', expected '{}'", header, expected),
+            ));
+        }
+        let mut book = Book::new();
+        for line in lines {
+            let line = line?;
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens.len() < 6 {
+                continue;
+            }
+            let parse_error =
+                || io::Error::new(ErrorKind::InvalidData, format!("Invalid line {}", line));
+            let key = u64::from_str_radix(tokens[0], 16).map_err(|_| parse_error())?;
+            let mut numbers = [0u32; 4];
+            for (number, token) in numbers.iter_mut().zip(tokens[1..5].iter()) {
+                *number = token.parse().map_err(|_| parse_error())?;
+            }
+            let book_move = BookMove {
+                action: Action::from_string(tokens[5..].join(" ")),
+                weight: numbers[0],
+                wins: numbers[1],
+                draws: numbers[2],
+                losses: numbers[3],
+            };
+            book.add_canonical(key, book_move);
+        }
+        Ok(book)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "{} {} {}",
+            BOOK_HEADER,
+            BOOK_VERSION,
+            zobrist_fingerprint()
+        )?;
+        let mut keys: Vec<&u64> = self.positions.keys().collect();
+        keys.sort();
+        for key in keys {
+            for m in self.positions[key].iter() {
+                writeln!(
+                    writer,
+                    "{:016x} {} {} {} {} {}",
+                    key,
+                    m.weight,
+                    m.wins,
+                    m.draws,
+                    m.losses,
+                    m.action.to_string()
+                )?;
+            }
+        }
+        writer.flush()
+    }
+
+    /// Number of positions in the book
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn move_count(&self) -> usize {
+        self.positions.values().map(|moves| moves.len()).sum()
+    }
+
+    /// Adds `weight` to `action` in `state` and counts `result` for it, `None` being a draw
+    pub fn add(&mut self, state: &GameState, action: Action, weight: u32, result: Option<Color>) {
+        let sym = state.canonical_symmetry();
+        let canonical = state.transformed(sym);
+        let (wins, losses) = match result {
+            Some(color) if color == state.color_to_move => (1, 0),
+            Some(_) => (0, 1),
+            None => (0, 0),
+        };
+        let book_move = BookMove {
+            action: transform_action(sym, action),
+            weight,
+            wins,
+            draws: 1 - wins - losses,
+            losses,
+        };
+        self.add_canonical(
+            canonical.hash ^ obstacle_hash(canonical.obstacles),
+            book_move,
+        );
+    }
+
+    fn add_canonical(&mut self, key: u64, book_move: BookMove) {
+        let moves = self.positions.entry(key).or_default();
+        match moves.iter_mut().find(|m| m.action == book_move.action) {
+            Some(m) => m.add(&book_move),
+            None => moves.push(book_move),
+        }
+    }
+
+    pub fn merge(&mut self, other: &Book) {
+        for (&key, moves) in other.positions.iter() {
+            for book_move in moves.iter() {
+                self.add_canonical(key, *book_move);
+            }
+        }
+    }
+
+    /// All book moves of `state` in its own orientation, most played first
+    pub fn moves(&self, state: &GameState) -> Vec<BookMove> {
+        let sym = state.canonical_symmetry();
+        let inverse = inverse_symmetry(sym);
+        let mut res: Vec<BookMove> = self
+            .positions
+            .get(&state.canonical_hash())
+            .map_or_else(Vec::new, |moves| moves.clone());
+        for m in res.iter_mut() {
+            m.action = transform_action(inverse, m.action);
+        }
+        res.sort_by_key(|m| std::cmp::Reverse(m.weight));
+        res
+    }
+
+    /// Picks one of the book moves that have been played often enough and scored well enough,
+    /// with a probability proportional to its weight
+    pub fn probe<R: Rng>(&self, state: &GameState, rng: &mut R) -> Option<Action> {
+        let mut legal_moves = ActionList::default();
+        calculate_legal_moves(state, &mut legal_moves);
+        let candidates: Vec<BookMove> = self
+            .moves(state)
+            .into_iter()
+            .filter(|m| {
+                m.weight > 0
+                    && m.games() >= MIN_BOOK_GAMES
+                    && m.score() >= MIN_BOOK_SCORE
+                    && legal_moves.find_action(m.action).is_some()
+            })
+            .collect();
+        let total_weight: u32 = candidates.iter().map(|m| m.weight).sum();
+        if total_weight == 0 {
+            return None;
+        }
+        let mut pick = rng.gen_range(0, total_weight);
+        for m in candidates.iter() {
+            if pick < m.weight {
+                return Some(m.action);
+            }
+            pick -= m.weight;
+        }
+        unreachable!()
+    }
+}
+
+/// Collects the early moves of finished games into a book
+pub struct BookBuilder {
+    pub book: Book,
+    /// Moves made at or after this ply are not added
+    pub max_ply: u8,
+}
+
+impl BookBuilder {
+    pub fn new(max_ply: u8) -> BookBuilder {
+        BookBuilder {
+            book: Book::new(),
+            max_ply,
+        }
+    }
+
+    pub fn add_game(&mut self, start: &GameState, actions: &[Action], result: Option<Color>) {
+        let mut state = start.clone();
+        for &action in actions.iter() {
+            if state.ply >= self.max_ply {
+                break;
+            }
+            self.book.add(&state, action, 1, result);
+            state.make_action(action);
+        }
+    }
+
+    /// Adds all games of a fen log written by the referee. The moves are recovered from
+    /// consecutive positions, games that can not be replayed are skipped. Returns the amount of added games
+    pub fn add_fen_log(&mut self, path: &str) -> io::Result<usize> {
+        if !Path::new(path).is_file() {
+            return Err(io::Error::new(ErrorKind::NotFound, path.to_owned()));
+        }
+        let mut games = 0;
+        let mut states: Vec<GameState> = Vec::new();
+        for (state, desc) in FenReader::from_path(path) {
+            let game_over = desc.trim() == "GameOver";
+            states.push(state);
+            if !game_over {
+                continue;
+            }
+            let last = states.last().unwrap();
+            if is_game_finished(last) {
+                let result = get_result(last);
+                let actions: Option<Vec<Action>> = states
+                    .windows(2)
+                    .take_while(|pair| pair[0].ply < self.max_ply)
+                    .map(|pair| find_action(&pair[0], &pair[1]))
+                    .collect();
+                if let Some(actions) = actions {
+                    self.add_game(&states[0], &actions, result);
+                    games += 1;
+                }
+            }
+            states.clear();
+        }
+        Ok(games)
+    }
+
+    /// Plays a game from `start` with `searcher` and adds it. Returns the winner
+    pub fn add_selfplay_game(
+        &mut self,
+        searcher: &mut Searcher,
+        start: &GameState,
+    ) -> Option<Color> {
+        let mut state = start.clone();
+        let mut actions = Vec::with_capacity(60);
+        while !is_game_finished(&state) {
+            let action = searcher.search_move(&state);
+            actions.push(action);
+            state.make_action(action);
+        }
+        let result = get_result(&state);
+        self.add_game(start, &actions, result);
+        result
+    }
+}
+
+/// Legal move that leads from `from` to `to`
+fn find_action(from: &GameState, to: &GameState) -> Option<Action> {
+    let mut legal_moves = ActionList::default();
+    calculate_legal_moves(from, &mut legal_moves);
+    (0..legal_moves.size)
+        .map(|i| legal_moves[i])
+        .find(|&action| {
+            let mut state = from.clone();
+            state.make_action(action);
+            state == *to
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Book, BookBuilder, MIN_BOOK_GAMES};
+    use crate::search::Searcher;
+    use crate::searchlimits::SearchLimits;
+    use game_sdk::gamerules::calculate_legal_moves;
+    use game_sdk::symmetry::transform_action;
+    use game_sdk::{ActionList, Color, GameState};
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn symmetric_games_share_book_entries() {
+        let mut start = GameState::new();
+        start.obstacles = (1u128 << 5) | (1u128 << 49) | (1u128 << 107);
+        let mut al = ActionList::default();
+        calculate_legal_moves(&start, &mut al);
+        let action = al[al.size - 1];
+        let mut builder = BookBuilder::new(4);
+        for sym in 0..3 {
+            builder.add_game(
+                &start.transformed(sym),
+                &[transform_action(sym, action)],
+                Some(Color::RED),
+            );
+        }
+        let path = std::env::temp_dir().join(format!("book-{}.txt", std::process::id()));
+        builder.book.save(&path).unwrap();
+        let book = Book::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(book.len(), 1);
+        let mut rng = SmallRng::seed_from_u64(0);
+        assert_eq!(book.probe(&start, &mut rng), Some(action));
+        let mirrored = start.transformed(7);
+        assert_eq!(
+            book.probe(&mirrored, &mut rng),
+            Some(transform_action(7, action))
+        );
+    }
+
+    #[test]
+    fn selfplay_books_answer_the_first_move() {
+        let mut searcher = Searcher::deterministic(SearchLimits::none().with_depth(2));
+        searcher.listeners.clear();
+        let mut rng = SmallRng::seed_from_u64(0);
+        // the first move only gets into the book if red does not lose with it
+        let (start, result) = loop {
+            let start = GameState::random_with(&mut rng);
+            let mut builder = BookBuilder::new(2);
+            let result = builder.add_selfplay_game(&mut searcher, &start);
+            if result != Some(Color::BLUE) {
+                break (start, result);
+            }
+        };
+        let mut builder = BookBuilder::new(2);
+        for _ in 0..MIN_BOOK_GAMES {
+            assert_eq!(builder.add_selfplay_game(&mut searcher, &start), result);
+        }
+        let action = builder.book.probe(&start, &mut rng).unwrap();
+        assert_eq!(action, searcher.search_move(&start));
+    }
+}
@@ -1,6 +1,29 @@
-use game_sdk::Action;
+use game_sdk::{Action, PieceType};
+use std::fmt::{Display, Formatter, Result};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 
 pub const HASH_SIZE: usize = 64; //IN MB
+
+/// Returned if the memory for a cache can not be allocated
+#[derive(Debug)]
+pub struct AllocationError {
+    /// Requested size in MB
+    pub size: usize,
+}
+impl Display for AllocationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Could not allocate {} MB for the cache", self.size)
+    }
+}
+
+/// Amount of 64 byte buckets fitting into `size` MB, at least one
+fn buckets_for_size(size: usize) -> std::result::Result<usize, AllocationError> {
+    let bytes = size
+        .checked_mul(1024 * 1024)
+        .ok_or(AllocationError { size })?;
+    Ok((bytes / 64).max(1))
+}
+
 pub struct Cache {
     pub entries: usize,
     pub buckets: usize,
@@ -8,14 +31,29 @@ pub struct Cache {
 }
 impl Cache {
     pub fn with_size(size: usize) -> Cache {
-        let buckets = 1024 * 1024 * size / 64;
+        Cache::try_with_size(size).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Size in MB, rounded down to whole buckets
+    pub fn try_with_size(size: usize) -> std::result::Result<Cache, AllocationError> {
+        let buckets = buckets_for_size(size)?;
         let entries = buckets * 3;
-        let cache = vec![CacheBucket([CacheEntry::invalid(); 3]); buckets];
-        Cache {
+        let mut cache = Vec::new();
+        cache
+            .try_reserve_exact(buckets)
+            .map_err(|_| AllocationError { size })?;
+        cache.resize(buckets, CacheBucket([CacheEntry::invalid(); 3]));
+        Ok(Cache {
             entries,
             buckets,
             cache,
-        }
+        })
+    }
+
+    /// Replaces the cache with an empty one of `size` MB. The old cache is kept if that fails
+    pub fn resize(&mut self, size: usize) -> std::result::Result<(), AllocationError> {
+        *self = Cache::try_with_size(size)?;
+        Ok(())
     }
     pub fn fill_status(&self) -> usize {
         if self.entries < 1000 {
@@ -40,6 +78,12 @@ impl Cache {
         (full as f64 / counted_entries as f64 * 1000.0) as usize
     }
 
+    pub fn clear(&mut self) {
+        for bucket in self.cache.iter_mut() {
+            *bucket = CacheBucket([CacheEntry::invalid(); 3]);
+        }
+    }
+
     pub fn lookup(&self, hash: u64) -> Option<CacheEntry> {
         self.cache[hash as usize % self.buckets].probe(hash)
     }
@@ -144,14 +188,29 @@ pub struct EvalCache {
 }
 impl EvalCache {
     pub fn with_size(size: usize) -> EvalCache {
-        let buckets = 1024 * 1024 * size / 64;
+        EvalCache::try_with_size(size).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Size in MB, rounded down to whole buckets
+    pub fn try_with_size(size: usize) -> std::result::Result<EvalCache, AllocationError> {
+        let buckets = buckets_for_size(size)?;
         let entries = buckets * 5;
-        let cache = vec![EvalCacheBucket([EvalCacheEntry::invalid(); 5]); buckets];
-        EvalCache {
+        let mut cache = Vec::new();
+        cache
+            .try_reserve_exact(buckets)
+            .map_err(|_| AllocationError { size })?;
+        cache.resize(buckets, EvalCacheBucket([EvalCacheEntry::invalid(); 5]));
+        Ok(EvalCache {
             entries,
             buckets,
             cache,
-        }
+        })
+    }
+
+    /// Replaces the cache with an empty one of `size` MB. The old cache is kept if that fails
+    pub fn resize(&mut self, size: usize) -> std::result::Result<(), AllocationError> {
+        *self = EvalCache::try_with_size(size)?;
+        Ok(())
     }
     pub fn fill_status(&self) -> usize {
         if self.entries < 1000 {
@@ -176,6 +235,12 @@ impl EvalCache {
         (full as f64 / counted_entries as f64 * 1000.0) as usize
     }
 
+    pub fn clear(&mut self) {
+        for bucket in self.cache.iter_mut() {
+            *bucket = EvalCacheBucket([EvalCacheEntry::invalid(); 5]);
+        }
+    }
+
     pub fn lookup(&self, hash: u64) -> Option<EvalCacheEntry> {
         self.cache[hash as usize % self.buckets].probe(hash)
     }
@@ -264,3 +329,288 @@ impl EvalCacheEntry {
         }
     }
 }
+
+/// Packs an action into the lowest 19 bits: 2 bits kind, 3 bits piece type, 7 bits from and 7 bits to
+pub fn pack_action(action: Action) -> u32 {
+    match action {
+        Action::SkipMove => 0,
+        Action::SetMove(piece_type, to) => 1 | (piece_type as u32) << 2 | (to as u32) << 12,
+        Action::DragMove(piece_type, from, to) => {
+            2 | (piece_type as u32) << 2 | (from as u32) << 5 | (to as u32) << 12
+        }
+    }
+}
+
+pub fn unpack_action(packed: u32) -> Action {
+    let piece_type = match (packed >> 2) & 0b111 {
+        0 => PieceType::BEE,
+        1 => PieceType::ANT,
+        2 => PieceType::BEETLE,
+        3 => PieceType::GRASSHOPPER,
+        _ => PieceType::SPIDER,
+    };
+    let from = ((packed >> 5) & 0x7F) as u8;
+    let to = ((packed >> 12) & 0x7F) as u8;
+    match packed & 0b11 {
+        1 => Action::SetMove(piece_type, to),
+        2 => Action::DragMove(piece_type, from, to),
+        _ => Action::SkipMove,
+    }
+}
+
+#[inline(always)]
+fn prefetch_address<T>(address: &T) {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch(address as *const T as *const i8, _MM_HINT_T0);
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = address;
+}
+
+impl Cache {
+    /// Loads the bucket of `hash` into the cpu cache, so a following lookup is faster
+    pub fn prefetch(&self, hash: u64) {
+        prefetch_address(&self.cache[hash as usize % self.buckets]);
+    }
+}
+
+impl EvalCache {
+    /// Loads the bucket of `hash` into the cpu cache, so a following lookup is faster
+    pub fn prefetch(&self, hash: u64) {
+        prefetch_address(&self.cache[hash as usize % self.buckets]);
+    }
+}
+
+// Layout of the data word of a `SharedCache` entry
+const SHARED_SCORE_SHIFT: u64 = 19;
+const SHARED_DEPTH_SHIFT: u64 = 35;
+const SHARED_ALPHA_BIT: u64 = 1 << 43;
+const SHARED_BETA_BIT: u64 = 1 << 44;
+const SHARED_PLIES_SHIFT: u64 = 45;
+const SHARED_GENERATION_SHIFT: u64 = 51;
+const SHARED_VALID_BIT: u64 = 1 << 63;
+const SHARED_ENTRIES: usize = 4;
+
+/// Transposition table that can be used by several threads at once.
+/// Every entry is stored as two atomic words, the data and the hash xor the data.
+/// A torn write, where key and data stem from different writes, then fails the hash check on lookup
+pub struct SharedCache {
+    pub entries: usize,
+    pub buckets: usize,
+    pub cache: Vec<SharedCacheBucket>,
+    generation: AtomicU8,
+}
+impl SharedCache {
+    pub fn with_size(size: usize) -> SharedCache {
+        SharedCache::try_with_size(size).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Size in MB, rounded down to whole buckets
+    pub fn try_with_size(size: usize) -> std::result::Result<SharedCache, AllocationError> {
+        let buckets = buckets_for_size(size)?;
+        let mut cache = Vec::new();
+        cache
+            .try_reserve_exact(buckets)
+            .map_err(|_| AllocationError { size })?;
+        cache.resize_with(buckets, SharedCacheBucket::default);
+        Ok(SharedCache {
+            entries: buckets * SHARED_ENTRIES,
+            buckets,
+            cache,
+            generation: AtomicU8::new(0),
+        })
+    }
+
+    /// Replaces the cache with an empty one of `size` MB. The old cache is kept if that fails
+    pub fn resize(&mut self, size: usize) -> std::result::Result<(), AllocationError> {
+        *self = SharedCache::try_with_size(size)?;
+        Ok(())
+    }
+
+    pub fn clear(&self) {
+        for bucket in self.cache.iter() {
+            for word in bucket.0.iter() {
+                word.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Has to be called before every search, entries of older searches are replaced first
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn fill_status(&self) -> usize {
+        if self.entries < 1000 {
+            return 1000;
+        }
+        let generation = self.generation.load(Ordering::Relaxed);
+        let mut full = 0;
+        for bucket in self.cache.iter().take(1000 / SHARED_ENTRIES) {
+            for slot in 0..SHARED_ENTRIES {
+                let data = bucket.0[2 * slot + 1].load(Ordering::Relaxed);
+                if data & SHARED_VALID_BIT != 0 && entry_generation(data) == generation {
+                    full += 1;
+                }
+            }
+        }
+        full
+    }
+
+    /// Loads the bucket of `hash` into the cpu cache, so a following lookup is faster
+    pub fn prefetch(&self, hash: u64) {
+        prefetch_address(&self.cache[hash as usize % self.buckets]);
+    }
+
+    pub fn lookup(&self, hash: u64) -> Option<CacheEntry> {
+        let bucket = &self.cache[hash as usize % self.buckets];
+        for slot in 0..SHARED_ENTRIES {
+            let key = bucket.0[2 * slot].load(Ordering::Relaxed);
+            let data = bucket.0[2 * slot + 1].load(Ordering::Relaxed);
+            if data & SHARED_VALID_BIT != 0 && key ^ data == hash {
+                return Some(unpack_entry(hash, data));
+            }
+        }
+        None
+    }
+
+    /// Replaces the entry of the same position, an empty one or the one with the lowest depth,
+    /// where entries of older searches count as less deep
+    pub fn insert(&self, hash: u64, ce: CacheEntry) {
+        let bucket = &self.cache[hash as usize % self.buckets];
+        let generation = self.generation.load(Ordering::Relaxed);
+        let mut replace = 0;
+        let mut lowest_value = i32::MAX;
+        for slot in 0..SHARED_ENTRIES {
+            let key = bucket.0[2 * slot].load(Ordering::Relaxed);
+            let data = bucket.0[2 * slot + 1].load(Ordering::Relaxed);
+            if data & SHARED_VALID_BIT == 0 || key ^ data == hash {
+                replace = slot;
+                break;
+            }
+            let age = generation.wrapping_sub(entry_generation(data)) as i32;
+            let value = ((data >> SHARED_DEPTH_SHIFT) & 0xFF) as i32 - 8 * age;
+            if value < lowest_value {
+                lowest_value = value;
+                replace = slot;
+            }
+        }
+        let data = pack_entry(&ce, generation);
+        bucket.0[2 * replace + 1].store(data, Ordering::Relaxed);
+        bucket.0[2 * replace].store(hash ^ data, Ordering::Relaxed);
+    }
+}
+
+/// Four entries of two words each, filling a cache line
+#[repr(align(64))]
+#[derive(Default)]
+pub struct SharedCacheBucket([AtomicU64; 2 * SHARED_ENTRIES]);
+
+fn entry_generation(data: u64) -> u8 {
+    (data >> SHARED_GENERATION_SHIFT) as u8
+}
+
+fn pack_entry(ce: &CacheEntry, generation: u8) -> u64 {
+    let mut data = SHARED_VALID_BIT
+        | pack_action(ce.action) as u64
+        | (ce.score as u16 as u64) << SHARED_SCORE_SHIFT
+        | (ce.depth as u64) << SHARED_DEPTH_SHIFT
+        | (ce.plies as u64 & 0x3F) << SHARED_PLIES_SHIFT
+        | (generation as u64) << SHARED_GENERATION_SHIFT;
+    if ce.alpha {
+        data |= SHARED_ALPHA_BIT;
+    }
+    if ce.beta {
+        data |= SHARED_BETA_BIT;
+    }
+    data
+}
+
+fn unpack_entry(hash: u64, data: u64) -> CacheEntry {
+    CacheEntry {
+        upper_hash: (hash >> 32) as u32,
+        lower_hash: (hash & 0xFFFFFFFF) as u32,
+        action: unpack_action((data & 0x7FFFF) as u32),
+        score: (data >> SHARED_SCORE_SHIFT) as u16 as i16,
+        depth: (data >> SHARED_DEPTH_SHIFT) as u8,
+        alpha: data & SHARED_ALPHA_BIT != 0,
+        beta: data & SHARED_BETA_BIT != 0,
+        plies: ((data >> SHARED_PLIES_SHIFT) & 0x3F) as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pack_action, unpack_action, Cache, CacheEntry, EvalCache, SharedCache};
+    use game_sdk::{Action, PieceType};
+    use std::sync::atomic::Ordering;
+
+    fn entry(action: Action, score: i16) -> CacheEntry {
+        CacheEntry {
+            upper_hash: 0,
+            lower_hash: 0,
+            action,
+            score,
+            depth: 12,
+            alpha: false,
+            beta: true,
+            plies: 59,
+        }
+    }
+
+    #[test]
+    fn shared_entries_roundtrip() {
+        for action in [
+            Action::SkipMove,
+            Action::SetMove(PieceType::SPIDER, 120),
+            Action::DragMove(PieceType::BEETLE, 0, 120),
+            Action::DragMove(PieceType::BEE, 120, 1),
+        ]
+        .iter()
+        {
+            assert_eq!(unpack_action(pack_action(*action)), *action);
+        }
+        let cache = SharedCache::with_size(1);
+        let hash = 0xDEAD_BEEF_1234_5678;
+        cache.insert(hash, entry(Action::SetMove(PieceType::ANT, 60), -29_999));
+        let ce = cache.lookup(hash).unwrap();
+        assert!(ce.valid_hash(hash));
+        assert_eq!(ce.action, Action::SetMove(PieceType::ANT, 60));
+        assert_eq!(ce.score, -29_999);
+        assert_eq!(
+            (ce.depth, ce.alpha, ce.beta, ce.plies),
+            (12, false, true, 59)
+        );
+        assert!(cache.lookup(hash ^ 1).is_none());
+    }
+
+    #[test]
+    fn torn_writes_are_detected() {
+        let cache = SharedCache::with_size(1);
+        let hash = 17 * cache.buckets as u64 + 3;
+        let other = 5 * cache.buckets as u64 + 3;
+        cache.insert(hash, entry(Action::SkipMove, 10));
+        cache.insert(other, entry(Action::SetMove(PieceType::BEE, 5), 20));
+        // key of the first write combined with the data of the second one
+        let bucket = &cache.cache[hash as usize % cache.buckets];
+        let data = bucket.0[3].load(Ordering::Relaxed);
+        bucket.0[1].store(data, Ordering::Relaxed);
+        assert!(cache.lookup(hash).is_none());
+    }
+
+    #[test]
+    fn oversized_caches_are_rejected() {
+        let mut cache = Cache::with_size(1);
+        assert_eq!(cache.resize(usize::MAX).unwrap_err().size, usize::MAX);
+        assert_eq!(cache.buckets, 1024 * 1024 / 64);
+        let mut eval_cache = EvalCache::with_size(1);
+        assert!(eval_cache.resize(usize::MAX).is_err());
+        assert_eq!(eval_cache.buckets, 1024 * 1024 / 64);
+        let mut shared_cache = SharedCache::with_size(1);
+        // fits into usize, but not into memory
+        assert!(shared_cache.resize(usize::MAX / 1024 / 1024).is_err());
+        assert_eq!(shared_cache.buckets, 1024 * 1024 / 64);
+    }
+}
@@ -1,7 +1,17 @@
+use crate::evaluator::Evaluator;
+use crate::obstacles::ObstacleInfo;
 use game_sdk::bitboard::get_neighbours;
-use game_sdk::gamerules::are_connected_in_swarm;
+use game_sdk::gamerules::{
+    append_spider_destinations, are_connected_in_swarm, get_ant_destinations,
+    get_beetle_accessible_neighbours, get_grasshopper_destinations,
+};
 use game_sdk::{bitboard, get_accessible_neighbors, Color, GameState, PieceType};
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::Path;
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct EvaluationParameters {
     pub tempo_bonus: f64,
     pub pinned_factor: f64,
@@ -13,10 +23,25 @@ pub struct EvaluationParameters {
     pub free_factor_phased: f64,
     pub free_own_beetle: f64,
     pub free_own: f64,
+    /// Per destination of the unpinned pieces of a type
+    pub ant_mobility: f64,
+    pub beetle_mobility: f64,
+    pub grasshopper_mobility: f64,
+    pub spider_mobility: f64,
+    /// Per piece that can move next to the enemy bee with its next move
+    pub bee_attackers: f64,
+    pub bee_attackers_phased: f64,
+    /// Per neighbour of the bee that is an obstacle or off the board. Unlike pieces these never move away
+    pub blocked_bee_factor: f64,
+    /// Per beetle in a stack that has one of our beetles on top
+    pub stack_control: f64,
+    /// Per piece that has not been set yet
+    pub in_hand: f64,
+    pub in_hand_phased: f64,
 }
 
 impl EvaluationParameters {
-    pub const fn from_array(params: [f64; 10]) -> EvaluationParameters {
+    pub const fn from_array(params: [f64; NUM_PARAMETERS]) -> EvaluationParameters {
         EvaluationParameters {
             tempo_bonus: params[0],
             pinned_factor: params[1],
@@ -28,15 +53,146 @@ impl EvaluationParameters {
             free_factor_phased: params[7],
             free_own_beetle: params[8],
             free_own: params[9],
+            ant_mobility: params[10],
+            beetle_mobility: params[11],
+            grasshopper_mobility: params[12],
+            spider_mobility: params[13],
+            bee_attackers: params[14],
+            bee_attackers_phased: params[15],
+            blocked_bee_factor: params[16],
+            stack_control: params[17],
+            in_hand: params[18],
+            in_hand_phased: params[19],
         }
     }
+
+    /// Whether the mobility or bee attacker features have a weight. They need the destinations
+    /// of every piece, which are skipped otherwise
+    pub fn uses_mobility(&self) -> bool {
+        self.to_array()[10..16].iter().any(|&weight| weight != 0.)
+    }
+
+    pub fn to_array(&self) -> [f64; NUM_PARAMETERS] {
+        [
+            self.tempo_bonus,
+            self.pinned_factor,
+            self.ant_pin_factor,
+            self.bee_move_factor,
+            self.beetle_factor,
+            self.free_factor,
+            self.set_fields_factor,
+            self.free_factor_phased,
+            self.free_own_beetle,
+            self.free_own,
+            self.ant_mobility,
+            self.beetle_mobility,
+            self.grasshopper_mobility,
+            self.spider_mobility,
+            self.bee_attackers,
+            self.bee_attackers_phased,
+            self.blocked_bee_factor,
+            self.stack_control,
+            self.in_hand,
+            self.in_hand_phased,
+        ]
+    }
+
+    /// Reads `name = value` lines, empty lines and lines starting with `#` are skipped.
+    /// Every parameter has to be given exactly once
+    pub fn parse(text: &str) -> Result<EvaluationParameters, String> {
+        let mut params = [None; NUM_PARAMETERS];
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let name = parts.next().unwrap_or("").trim();
+            let value = parts
+                .next()
+                .ok_or_else(|| format!("Line {} is not of the form name = value", line_number + 1))?
+                .trim();
+            let index = PARAMETER_NAMES
+                .iter()
+                .position(|&n| n == name)
+                .ok_or_else(|| format!("Unknown parameter {}", name))?;
+            if params[index].is_some() {
+                return Err(format!("Parameter {} is given twice", name));
+            }
+            params[index] = Some(
+                value
+                    .parse::<f64>()
+                    .map_err(|_| format!("Invalid value {} for parameter {}", value, name))?,
+            );
+        }
+        let missing: Vec<&str> = PARAMETER_NAMES
+            .iter()
+            .zip(params.iter())
+            .filter(|(_, value)| value.is_none())
+            .map(|(name, _)| *name)
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("Missing parameters {}", missing.join(", ")));
+        }
+        let mut res = [0.; NUM_PARAMETERS];
+        for (value, param) in res.iter_mut().zip(params.iter()) {
+            *value = param.unwrap();
+        }
+        Ok(EvaluationParameters::from_array(res))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<EvaluationParameters, String> {
+        let text = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("Could not read {}: {}", path.as_ref().display(), e))?;
+        EvaluationParameters::parse(&text)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+/// One `name = value` line per parameter, the format read by `EvaluationParameters::parse`
+impl Display for EvaluationParameters {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (name, value) in PARAMETER_NAMES.iter().zip(self.to_array().iter()) {
+            writeln!(f, "{} = {}", name, value)?;
+        }
+        Ok(())
+    }
 }
 
+pub const NUM_PARAMETERS: usize = 20;
+pub const PARAMETER_NAMES: [&str; NUM_PARAMETERS] = [
+    "tempo_bonus",
+    "pinned_factor",
+    "ant_pin_factor",
+    "bee_move_factor",
+    "beetle_factor",
+    "free_factor",
+    "set_fields_factor",
+    "free_factor_phased",
+    "free_own_beetle",
+    "free_own",
+    "ant_mobility",
+    "beetle_mobility",
+    "grasshopper_mobility",
+    "spider_mobility",
+    "bee_attackers",
+    "bee_attackers_phased",
+    "blocked_bee_factor",
+    "stack_control",
+    "in_hand",
+    "in_hand_phased",
+];
+/// Feature values or contributions of one color, indexed like the parameters
+pub type Features = [f64; NUM_PARAMETERS];
 pub const COLOR_TO_MOVE: f64 = 12.0;
 
 // parameters originally chosen by hand, kept for historical purposes
 // pub const DEFAULT_ARRAY: [f64; 10] = [12.0, -6.0, 6.0, 4.0, -30.0, 12.0, 1.0, 24.0, 0.33, 0.25];
-pub const DEFAULT_ARRAY: [f64; 10] = [
+// the parameters from index 10 on are not tuned yet and stay disabled until they are
+pub const DEFAULT_ARRAY: [f64; NUM_PARAMETERS] = [
     4.465854911107787,
     -17.11870023666,
     0.6434493074867715,
@@ -47,23 +203,119 @@ pub const DEFAULT_ARRAY: [f64; 10] = [
     75.322281041304,
     -0.01999999999999995,
     0.49999999999999994,
+    0.0,
+    0.0,
+    0.0,
+    0.0,
+    0.0,
+    0.0,
+    0.0,
+    0.0,
+    0.0,
+    0.0,
 ];
 
 pub const DEFAULT: EvaluationParameters = EvaluationParameters::from_array(DEFAULT_ARRAY);
 
+/// Computes the obstacle data for every call, searches should use `evaluate_with_obstacles`
 pub fn evaluate(game_state: &GameState) -> i16 {
-    (evaluate_color(game_state, Color::RED, &DEFAULT)
-        - evaluate_color(game_state, Color::BLUE, &DEFAULT))
+    evaluate_with_obstacles(game_state, &ObstacleInfo::new(game_state.obstacles))
+}
+
+pub fn evaluate_with_obstacles(game_state: &GameState, info: &ObstacleInfo) -> i16 {
+    evaluate_with(game_state, &DEFAULT, info)
+}
+
+pub fn evaluate_with(
+    game_state: &GameState,
+    params: &EvaluationParameters,
+    info: &ObstacleInfo,
+) -> i16 {
+    debug_assert_eq!(game_state.obstacles, info.obstacles);
+    (evaluate_color(game_state, Color::RED, params, info)
+        - evaluate_color(game_state, Color::BLUE, params, info))
     .round() as i16
 }
 
 pub fn evaluate_with_parameters(game_state: &GameState, params: &EvaluationParameters) -> i16 {
-    (evaluate_color(game_state, Color::RED, params)
-        - evaluate_color(game_state, Color::BLUE, params))
-    .round() as i16
+    evaluate_with(game_state, params, &ObstacleInfo::new(game_state.obstacles))
+}
+
+/// The hand crafted evaluation as `Evaluator`, with the default or tuned parameters
+pub struct HandCrafted {
+    pub params: EvaluationParameters,
+    info: ObstacleInfo,
+}
+
+impl HandCrafted {
+    pub fn with_parameters(params: EvaluationParameters) -> HandCrafted {
+        HandCrafted {
+            params,
+            info: ObstacleInfo::default(),
+        }
+    }
+}
+
+impl Default for HandCrafted {
+    fn default() -> Self {
+        HandCrafted::with_parameters(DEFAULT)
+    }
+}
+
+impl Evaluator for HandCrafted {
+    fn evaluate(&mut self, game_state: &GameState) -> i16 {
+        let evaluation = evaluate_with(game_state, &self.params, &self.info);
+        if game_state.color_to_move == Color::RED {
+            evaluation
+        } else {
+            -evaluation
+        }
+    }
+
+    fn prepare(&mut self, game_state: &GameState) {
+        if self.info.obstacles != game_state.obstacles {
+            self.info = ObstacleInfo::new(game_state.obstacles);
+        }
+    }
+
+    fn trace(&self, game_state: &GameState) -> Option<EvalTrace> {
+        Some(evaluate_trace(game_state, &self.params))
+    }
+
+    fn name(&self) -> &'static str {
+        "handcrafted"
+    }
+}
+
+pub fn evaluate_color(
+    game_state: &GameState,
+    color: Color,
+    params: &EvaluationParameters,
+    info: &ObstacleInfo,
+) -> f64 {
+    contributions(
+        &features(game_state, color, info, params.uses_mobility()),
+        phase(game_state),
+        params,
+    )
+    .iter()
+    .sum()
 }
 
-pub fn evaluate_color(game_state: &GameState, color: Color, params: &EvaluationParameters) -> f64 {
+/// 0 at the start of the game, 1 at its end
+pub fn phase(game_state: &GameState) -> f64 {
+    game_state.ply as f64 / 60.
+}
+
+/// Values of the features of `color` that the parameters with the same index are multiplied with.
+/// Phased parameters have the same feature as their unphased counterpart.
+/// Without `with_mobility` the mobility and bee attacker features of unstacked pieces are left at 0
+pub fn features(
+    game_state: &GameState,
+    color: Color,
+    info: &ObstacleInfo,
+    with_mobility: bool,
+) -> Features {
     let occupied = game_state.occupied();
     let obstacles = game_state.obstacles;
 
@@ -71,12 +323,27 @@ pub fn evaluate_color(game_state: &GameState, color: Color, params: &EvaluationP
     let bee_index = bee.trailing_zeros() as usize;
     let bee_neighbors = get_neighbours(bee);
     let bee_moves = get_accessible_neighbors(occupied, obstacles, bee).count_ones() as f64;
-    let mut free_bee_fields = (bee_neighbors & !occupied & !obstacles).count_ones() as f64;
-    free_bee_fields += params.free_own
-        * (bee_neighbors & game_state.occupied[color as usize]).count_ones() as f64
-        + params.free_own_beetle
-            * (bee_neighbors & game_state.pieces[PieceType::BEETLE as usize][color as usize])
-                .count_ones() as f64;
+    let enemy_bee = game_state.pieces[PieceType::BEE as usize][color.swap() as usize];
+    let enemy_bee_index = enemy_bee.trailing_zeros() as usize;
+    let attack_squares = if enemy_bee_index <= 120 {
+        info.free_neighbours[enemy_bee_index] & !occupied
+    } else {
+        0
+    };
+    let (free_bee_neighbours, blocked_bee_fields) = if bee_index <= 120 {
+        (
+            info.free_neighbours[bee_index],
+            info.blocked_neighbours[bee_index] as f64,
+        )
+    } else {
+        (0, 0.)
+    };
+    let free_bee_fields = (free_bee_neighbours & !occupied).count_ones() as f64;
+    let own_bee_neighbours =
+        (bee_neighbors & game_state.occupied[color as usize]).count_ones() as f64;
+    let own_beetle_bee_neighbours = (bee_neighbors
+        & game_state.pieces[PieceType::BEETLE as usize][color as usize])
+        .count_ones() as f64;
     let our_set = get_neighbours(game_state.occupied[color as usize])
         & !obstacles
         & !occupied
@@ -102,6 +369,10 @@ pub fn evaluate_color(game_state: &GameState, color: Color, params: &EvaluationP
         ants ^= 1u128 << ant;
     }
 
+    let stacked = game_state.beetle_stack[0][Color::RED as usize]
+        | game_state.beetle_stack[0][Color::BLUE as usize];
+    let mut mobility = [0.; 5];
+    let mut bee_attackers = 0.;
     let mut pinned_pieces = 0.;
     for pt in [
         PieceType::BEE,
@@ -119,29 +390,299 @@ pub fn evaluate_color(game_state: &GameState, color: Color, params: &EvaluationP
             pieces ^= piece_bit;
             if !can_be_removed(piece_bit, occupied) {
                 pinned_pieces += 1.;
+            } else if with_mobility && piece_bit & stacked == 0 {
+                let destinations = destinations(*pt, piece_bit, occupied ^ piece_bit, obstacles);
+                mobility[*pt as usize] += destinations.count_ones() as f64;
+                if destinations & attack_squares > 0 && get_neighbours(piece_bit) & enemy_bee == 0 {
+                    bee_attackers += 1.;
+                }
             }
         }
     }
+    // our beetles on top of stacks are never pinned and can move to every neighbour
+    let mut stack_control = 0.;
+    let mut own_stacks = stacked & game_state.occupied[color as usize];
+    while own_stacks > 0 {
+        let stack_bit = 1u128 << own_stacks.trailing_zeros();
+        own_stacks ^= stack_bit;
+        let destinations = get_neighbours(stack_bit) & !obstacles;
+        mobility[PieceType::BEETLE as usize] += destinations.count_ones() as f64;
+        if destinations & attack_squares > 0 && get_neighbours(stack_bit) & enemy_bee == 0 {
+            bee_attackers += 1.;
+        }
+        for level in game_state.beetle_stack.iter() {
+            if (level[Color::RED as usize] | level[Color::BLUE as usize]) & stack_bit > 0 {
+                stack_control += 1.;
+            }
+        }
+    }
+    let in_hand: u8 = game_state.undeployed_counts[color as usize].iter().sum();
+    let in_hand = in_hand as f64;
 
-    let mut res = 0.;
-    res += params.free_factor * free_bee_fields
-        + params.bee_move_factor * bee_moves
-        + params.set_fields_factor * our_set_fields
-        + params.beetle_factor * beetle_on_bee
-        + params.ant_pin_factor * ant_pinning_enemies
-        + params.free_factor_phased * (game_state.ply as f64 / 60.) * free_bee_fields
-        + params.pinned_factor * pinned_pieces;
-    res += if game_state.color_to_move == color {
-        params.tempo_bonus
+    let mut res = [0.; NUM_PARAMETERS];
+    res[0] = if game_state.color_to_move == color {
+        1.
     } else {
         0.
     };
+    res[1] = pinned_pieces;
+    res[2] = ant_pinning_enemies;
+    res[3] = bee_moves;
+    res[4] = beetle_on_bee;
+    res[5] = free_bee_fields;
+    res[6] = our_set_fields;
+    res[7] = free_bee_fields;
+    res[8] = own_beetle_bee_neighbours;
+    res[9] = own_bee_neighbours;
+    res[10] = mobility[PieceType::ANT as usize];
+    res[11] = mobility[PieceType::BEETLE as usize];
+    res[12] = mobility[PieceType::GRASSHOPPER as usize];
+    res[13] = mobility[PieceType::SPIDER as usize];
+    res[14] = bee_attackers;
+    res[15] = bee_attackers;
+    res[16] = blocked_bee_fields;
+    res[17] = stack_control;
+    res[18] = in_hand;
+    res[19] = in_hand;
+    res
+}
+
+/// Share of every parameter in the evaluation of one color. Their sum is the evaluation
+pub fn contributions(features: &Features, phase: f64, params: &EvaluationParameters) -> Features {
+    // own pieces next to the bee count as a fraction of a free field
+    let free_factor = params.free_factor + params.free_factor_phased * phase;
+    [
+        params.tempo_bonus * features[0],
+        params.pinned_factor * features[1],
+        params.ant_pin_factor * features[2],
+        params.bee_move_factor * features[3],
+        params.beetle_factor * features[4],
+        params.free_factor * features[5],
+        params.set_fields_factor * features[6],
+        params.free_factor_phased * phase * features[7],
+        free_factor * params.free_own_beetle * features[8],
+        free_factor * params.free_own * features[9],
+        params.ant_mobility * features[10],
+        params.beetle_mobility * features[11],
+        params.grasshopper_mobility * features[12],
+        params.spider_mobility * features[13],
+        params.bee_attackers * features[14],
+        params.bee_attackers_phased * phase * features[15],
+        params.blocked_bee_factor * features[16],
+        params.stack_control * features[17],
+        params.in_hand * features[18],
+        params.in_hand_phased * phase * features[19],
+    ]
+}
+
+/// Derivative of the evaluation of one color by every parameter. It is the feature itself,
+/// except for the parameters that are multiplied with each other
+pub fn parameter_gradient(
+    features: &Features,
+    phase: f64,
+    params: &EvaluationParameters,
+) -> Features {
+    let mut res = *features;
+    res[7] *= phase;
+    res[15] *= phase;
+    res[19] *= phase;
+    let free_factor = params.free_factor + params.free_factor_phased * phase;
+    let free_own = params.free_own_beetle * features[8] + params.free_own * features[9];
+    res[5] += free_own;
+    res[7] += phase * free_own;
+    res[8] = free_factor * features[8];
+    res[9] = free_factor * features[9];
     res
 }
 
+/// Breakdown of an evaluation into its features
+#[derive(Clone)]
+pub struct EvalTrace {
+    pub phase: f64,
+    /// Indexed by color
+    pub features: [Features; 2],
+    pub contributions: [Features; 2],
+}
+
+impl EvalTrace {
+    /// Evaluation from red's view, before rounding
+    pub fn score(&self) -> f64 {
+        self.contributions[Color::RED as usize].iter().sum::<f64>()
+            - self.contributions[Color::BLUE as usize].iter().sum::<f64>()
+    }
+
+    /// Evaluation with other parameters, without looking at the position again
+    pub fn score_with_parameters(&self, params: &EvaluationParameters) -> f64 {
+        contributions(&self.features[Color::RED as usize], self.phase, params)
+            .iter()
+            .sum::<f64>()
+            - contributions(&self.features[Color::BLUE as usize], self.phase, params)
+                .iter()
+                .sum::<f64>()
+    }
+
+    /// Derivative of `score_with_parameters` by every parameter
+    pub fn gradient(&self, params: &EvaluationParameters) -> Features {
+        let red = parameter_gradient(&self.features[Color::RED as usize], self.phase, params);
+        let blue = parameter_gradient(&self.features[Color::BLUE as usize], self.phase, params);
+        let mut res = [0.; NUM_PARAMETERS];
+        for (r, (red, blue)) in res.iter_mut().zip(red.iter().zip(blue.iter())) {
+            *r = red - blue;
+        }
+        res
+    }
+}
+
+impl Display for EvalTrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (red, blue) = (Color::RED as usize, Color::BLUE as usize);
+        writeln!(
+            f,
+            "{:<22} {:>8} {:>8} {:>8} {:>8} {:>8}",
+            "feature", "red", "points", "blue", "points", "diff"
+        )?;
+        for (i, name) in PARAMETER_NAMES.iter().enumerate() {
+            writeln!(
+                f,
+                "{:<22} {:>8.2} {:>8.2} {:>8.2} {:>8.2} {:>8.2}",
+                name,
+                self.features[red][i],
+                self.contributions[red][i],
+                self.features[blue][i],
+                self.contributions[blue][i],
+                self.contributions[red][i] - self.contributions[blue][i]
+            )?;
+        }
+        write!(
+            f,
+            "phase {:.2}, total {:.2} (red view)",
+            self.phase,
+            self.score()
+        )
+    }
+}
+
+pub fn evaluate_trace(game_state: &GameState, params: &EvaluationParameters) -> EvalTrace {
+    let info = ObstacleInfo::new(game_state.obstacles);
+    let phase = phase(game_state);
+    let features = [
+        features(game_state, Color::RED, &info, true),
+        features(game_state, Color::BLUE, &info, true),
+    ];
+    let contributions = [
+        contributions(&features[0], phase, params),
+        contributions(&features[1], phase, params),
+    ];
+    EvalTrace {
+        phase,
+        features,
+        contributions,
+    }
+}
+
+/// Destinations of a piece that is not pinned. `occupied` must not contain the piece itself
+fn destinations(piece_type: PieceType, piece_bit: u128, occupied: u128, obstacles: u128) -> u128 {
+    match piece_type {
+        // the bee has its own feature
+        PieceType::BEE => 0,
+        PieceType::BEETLE => get_beetle_accessible_neighbours(occupied, obstacles, piece_bit),
+        PieceType::ANT => get_ant_destinations(occupied, obstacles, piece_bit),
+        PieceType::GRASSHOPPER => get_grasshopper_destinations(occupied, obstacles, piece_bit),
+        PieceType::SPIDER => {
+            let mut destinations = 0;
+            append_spider_destinations(
+                &mut destinations,
+                occupied,
+                obstacles,
+                piece_bit,
+                piece_bit,
+                3,
+            );
+            destinations
+        }
+    }
+}
+
 pub fn can_be_removed(from: u128, occupied: u128) -> bool {
     // check if field can be removed and swarm is still connected
     let occupied = occupied ^ from;
     let neighbours = bitboard::get_neighbours(from) & occupied;
     are_connected_in_swarm(occupied, neighbours)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        evaluate, evaluate_trace, EvaluationParameters, HandCrafted, DEFAULT, DEFAULT_ARRAY,
+    };
+    use crate::evaluator::Evaluator;
+    use game_sdk::gamerules::calculate_legal_moves;
+    use game_sdk::{ActionList, Color, GameState};
+
+    #[test]
+    fn trace_adds_up_to_evaluation() {
+        let mut state = GameState::new();
+        state.obstacles = (1u128 << 5) | (1u128 << 49) | (1u128 << 107);
+        let mut al = ActionList::default();
+        let mut params = DEFAULT_ARRAY;
+        params[7] *= 2.;
+        params[19] = 1.;
+        let params = EvaluationParameters::from_array(params);
+        let mut evaluator = HandCrafted::with_parameters(params.clone());
+        for ply in 0..30 {
+            calculate_legal_moves(&state, &mut al);
+            state.make_action(al[al.size / 2]);
+            // a single piece on the board has no neighbours to stay connected to
+            if ply == 0 {
+                continue;
+            }
+            let trace = evaluate_trace(&state, &DEFAULT);
+            assert_eq!(trace.score().round() as i16, evaluate(&state));
+            assert_eq!(
+                trace.score_with_parameters(&params),
+                evaluate_trace(&state, &params).score()
+            );
+            // the evaluator traces with its own parameters
+            let own_trace = evaluator.trace(&state).unwrap();
+            evaluator.prepare(&state);
+            let sign = if state.color_to_move == Color::RED {
+                1
+            } else {
+                -1
+            };
+            assert_eq!(
+                own_trace.score().round() as i16,
+                sign * evaluator.evaluate(&state)
+            );
+            let gradient = trace.gradient(&params);
+            for (i, derivative) in gradient.iter().enumerate() {
+                let (mut lower, mut upper) = (params.to_array(), params.to_array());
+                lower[i] -= 0.5;
+                upper[i] += 0.5;
+                let difference = trace
+                    .score_with_parameters(&EvaluationParameters::from_array(upper))
+                    - trace.score_with_parameters(&EvaluationParameters::from_array(lower));
+                assert!((difference - derivative).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn parameter_file_roundtrip() {
+        let text = DEFAULT.to_string();
+        assert_eq!(EvaluationParameters::parse(&text), Ok(DEFAULT));
+        let commented = format!(
+            "# tuned\n\n{}",
+            text.replace("tempo_bonus = ", "tempo_bonus= ")
+        );
+        assert_eq!(EvaluationParameters::parse(&commented), Ok(DEFAULT));
+        let missing = text.replace("stack_control", "# stack_control");
+        assert!(EvaluationParameters::parse(&missing)
+            .unwrap_err()
+            .contains("stack_control"));
+        let unknown = format!("{}queen_factor = 1", text);
+        assert!(EvaluationParameters::parse(&unknown)
+            .unwrap_err()
+            .contains("queen_factor"));
+    }
+}
@@ -0,0 +1,54 @@
+use crate::evaluation::{EvalTrace, EvaluationParameters, HandCrafted};
+use crate::nnue::{Network, NnueState};
+use game_sdk::{Action, GameState};
+use std::sync::Arc;
+
+/// Scale of the logistic curve that maps scores to win probabilities, the one the parameters are tuned with
+pub const WIN_PROBABILITY_K: f64 = 1.5;
+
+/// Static evaluation that the searches can be configured with
+pub trait Evaluator: Send {
+    /// Score of `game_state` from the view of the player to move
+    fn evaluate(&mut self, game_state: &GameState) -> i16;
+
+    /// Called with the root of a search before it is evaluated, or with every state
+    /// that is evaluated without following the moves leading to it
+    fn prepare(&mut self, _game_state: &GameState) {}
+
+    /// Called with the state before `action` is made on it during a search
+    fn make_action(&mut self, _game_state: &GameState, _action: Action) {}
+
+    fn unmake_action(&mut self) {}
+
+    /// Terms of the evaluation of `game_state`, for evaluators that are made of terms
+    fn trace(&self, _game_state: &GameState) -> Option<EvalTrace> {
+        None
+    }
+
+    fn name(&self) -> &'static str;
+}
+
+/// Expected result of the player to move, 1 is a win and 0 a loss
+pub fn win_probability(score: i16) -> f32 {
+    (1. / (1. + 10f64.powf(-WIN_PROBABILITY_K * score as f64 / 400.))) as f32
+}
+
+/// Evaluator for the value of an `evaluator` or `evalfile` option. An empty value or
+/// `handcrafted` is the hand crafted evaluation, every other value is the file of a network
+pub fn evaluator_from_option(value: &str) -> Result<Box<dyn Evaluator>, String> {
+    match value {
+        "" | "handcrafted" => Ok(Box::new(HandCrafted::default())),
+        path => {
+            let network =
+                Network::load(path).map_err(|e| format!("Could not load {}: {}", path, e))?;
+            Ok(Box::new(NnueState::new(Arc::new(network))))
+        }
+    }
+}
+
+/// Hand crafted evaluation with the parameters of the file at `path`, which are printed
+pub fn parameter_evaluator(path: &str) -> Result<Box<dyn Evaluator>, String> {
+    let params = EvaluationParameters::load(path)?;
+    print!("Evaluation parameters from {}:\n{}", path, params);
+    Ok(Box::new(HandCrafted::with_parameters(params)))
+}
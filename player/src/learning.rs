@@ -0,0 +1,258 @@
+use crate::cache::{pack_action, unpack_action, Cache, CacheEntry};
+use game_sdk::{zobrist_fingerprint, Action, GameState};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+pub const LEARNING_MAGIC: [u8; 8] = *b"HIVELRN\0";
+/// Has to be increased whenever the file layout changes
+pub const LEARNING_VERSION: u32 = 1;
+pub const DEFAULT_LEARNING_SIZE: usize = 100_000;
+/// Results of shallower searches are found again quickly and not worth storing
+pub const MIN_LEARNING_DEPTH: u8 = 6;
+// hash, obstacles, action, score, depth, plies
+const RECORD_SIZE: usize = 8 + 16 + 4 + 2 + 1 + 1;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LearningEntry {
+    pub action: Action,
+    /// Exact score from the view of the player to move, never a mate score
+    pub score: i16,
+    pub depth: u8,
+    pub plies: u8,
+}
+
+/// Search results that are kept across games and processes.
+/// The obstacles are not part of `GameState::hash`, so they are stored next to it
+pub struct LearningStore {
+    pub path: PathBuf,
+    pub max_entries: usize,
+    entries: HashMap<(u64, u128), LearningEntry>,
+    /// Set by every change that has not been saved yet
+    modified: bool,
+}
+
+impl LearningStore {
+    pub fn new<P: AsRef<Path>>(path: P, max_entries: usize) -> LearningStore {
+        LearningStore {
+            path: path.as_ref().to_path_buf(),
+            max_entries,
+            entries: HashMap::new(),
+            modified: false,
+        }
+    }
+
+    /// Reads the store at `path`. A missing file results in an empty store, a file written
+    /// with other Zobrist keys or another layout is an error
+    pub fn load<P: AsRef<Path>>(path: P, max_entries: usize) -> io::Result<LearningStore> {
+        let mut store = LearningStore::new(path, max_entries);
+        let file = match File::open(&store.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(store),
+            Err(e) => return Err(e),
+        };
+        let mut reader = BufReader::new(file);
+        let mut header = [0u8; 8 + 4 + 8 + 8];
+        reader.read_exact(&mut header)?;
+        if header[..8] != LEARNING_MAGIC {
+            return Err(invalid_data("not a learning file".to_owned()));
+        }
+        let version = u32::from_le_bytes(read_array(&header[8..12]));
+        if version != LEARNING_VERSION {
+            return Err(invalid_data(format!(
+                "learning file has version {}, expected {}",
+                version, LEARNING_VERSION
+            )));
+        }
+        if u64::from_le_bytes(read_array(&header[12..20])) != zobrist_fingerprint() {
+            return Err(invalid_data(
+                "learning file was written with other hash keys".to_owned(),
+            ));
+        }
+        let count = u64::from_le_bytes(read_array(&header[20..28])) as usize;
+        let mut record = [0u8; RECORD_SIZE];
+        for _ in 0..count {
+            reader.read_exact(&mut record)?;
+            let hash = u64::from_le_bytes(read_array(&record[0..8]));
+            let obstacles = u128::from_le_bytes(read_array(&record[8..24]));
+            let entry = LearningEntry {
+                action: unpack_action(u32::from_le_bytes(read_array(&record[24..28]))),
+                score: i16::from_le_bytes(read_array(&record[28..30])),
+                depth: record[30],
+                plies: record[31],
+            };
+            store.insert_key((hash, obstacles), entry);
+        }
+        store.modified = false;
+        Ok(store)
+    }
+
+    /// Writes the store to a temporary file first, so an aborted write keeps the old file intact
+    pub fn save(&mut self) -> io::Result<()> {
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        writer.write_all(&LEARNING_MAGIC)?;
+        writer.write_all(&LEARNING_VERSION.to_le_bytes())?;
+        writer.write_all(&zobrist_fingerprint().to_le_bytes())?;
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for (&(hash, obstacles), entry) in self.entries.iter() {
+            writer.write_all(&hash.to_le_bytes())?;
+            writer.write_all(&obstacles.to_le_bytes())?;
+            writer.write_all(&pack_action(entry.action).to_le_bytes())?;
+            writer.write_all(&entry.score.to_le_bytes())?;
+            writer.write_all(&[entry.depth, entry.plies])?;
+        }
+        writer.flush()?;
+        drop(writer);
+        std::fs::rename(&tmp_path, &self.path)?;
+        self.modified = false;
+        Ok(())
+    }
+
+    /// Whether the store has changed since it was loaded or saved
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, state: &GameState) -> Option<LearningEntry> {
+        self.entries.get(&(state.hash, state.obstacles)).copied()
+    }
+
+    /// Stores `entry` unless the position is already known from a deeper search.
+    /// If the store is full, the shallowest entry is replaced if it is shallower than `entry`
+    pub fn insert(&mut self, state: &GameState, entry: LearningEntry) {
+        self.insert_key((state.hash, state.obstacles), entry);
+    }
+
+    fn insert_key(&mut self, key: (u64, u128), entry: LearningEntry) {
+        if let Some(old) = self.entries.get_mut(&key) {
+            if entry.depth >= old.depth {
+                *old = entry;
+                self.modified = true;
+            }
+            return;
+        }
+        if self.entries.len() >= self.max_entries {
+            let shallowest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.depth)
+                .map(|(k, e)| (*k, e.depth));
+            match shallowest {
+                Some((k, depth)) if depth < entry.depth => {
+                    self.entries.remove(&k);
+                }
+                _ => return,
+            }
+        }
+        self.entries.insert(key, entry);
+        self.modified = true;
+    }
+
+    /// Shrinks the store to at most `max_entries`, dropping the shallowest entries first
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries;
+        if self.entries.len() > max_entries {
+            self.modified = true;
+            let mut depths: Vec<u8> = self.entries.values().map(|e| e.depth).collect();
+            depths.sort_unstable_by(|a, b| b.cmp(a));
+            let min_depth = depths[max_entries];
+            let mut keep = max_entries - depths.iter().filter(|&&d| d > min_depth).count();
+            self.entries.retain(|_, e| {
+                if e.depth > min_depth {
+                    true
+                } else if e.depth == min_depth && keep > 0 {
+                    keep -= 1;
+                    true
+                } else {
+                    false
+                }
+            });
+        }
+    }
+
+    /// Copies all entries for the obstacles of `state` that are not behind it into the
+    /// transposition table. Returns the number of seeded entries
+    pub fn seed_cache(&self, cache: &mut Cache, state: &GameState) -> usize {
+        let mut seeded = 0;
+        for (&(hash, obstacles), entry) in self.entries.iter() {
+            if obstacles != state.obstacles || entry.plies < state.ply {
+                continue;
+            }
+            let ce = CacheEntry {
+                upper_hash: (hash >> 32) as u32,
+                lower_hash: (hash & 0xFFFFFFFF) as u32,
+                action: entry.action,
+                score: entry.score,
+                depth: entry.depth,
+                alpha: false,
+                beta: false,
+                plies: entry.plies,
+            };
+            cache.insert(hash, ce, state.ply);
+            seeded += 1;
+        }
+        seeded
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn read_array<T: Default + AsMut<[u8]>>(bytes: &[u8]) -> T {
+    let mut res = T::default();
+    res.as_mut().copy_from_slice(bytes);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LearningEntry, LearningStore, LEARNING_MAGIC};
+    use game_sdk::{Action, GameState, PieceType};
+
+    fn entry(depth: u8) -> LearningEntry {
+        LearningEntry {
+            action: Action::SetMove(PieceType::ANT, 60),
+            score: -42,
+            depth,
+            plies: 0,
+        }
+    }
+
+    #[test]
+    fn store_roundtrip_and_size_cap() {
+        let path = std::env::temp_dir().join(format!("learning-{}.bin", std::process::id()));
+        let state = GameState::new();
+        let mut other = GameState::new();
+        other.obstacles ^= 1;
+        let mut store = LearningStore::new(&path, 1);
+        store.insert(&state, entry(8));
+        // the store is full and the new entry is not deeper
+        store.insert(&other, entry(8));
+        assert_eq!(store.len(), 1);
+        store.insert(&other, entry(9));
+        assert_eq!(store.get(&state), None);
+        store.save().unwrap();
+
+        let loaded = LearningStore::load(&path, 10).unwrap();
+        assert_eq!(loaded.get(&other), Some(entry(9)));
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        // the version follows the magic
+        bytes[LEARNING_MAGIC.len()] += 1;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(LearningStore::load(&path, 10).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,8 +1,17 @@
 extern crate game_sdk;
 extern crate rand;
+pub mod book;
 pub mod cache;
 pub mod evaluation;
+pub mod evaluator;
+pub mod learning;
 pub mod mcts;
 pub mod moveordering;
+pub mod nnue;
+pub mod obstacles;
+pub mod options;
 pub mod search;
+pub mod searchhandle;
+pub mod searchinfo;
+pub mod searchlimits;
 pub mod timecontrol;
@@ -1,10 +1,11 @@
 use super::playout::playout;
+use super::priors::priors;
+use super::{MctsParameters, Selection};
+use crate::evaluator::{win_probability, Evaluator};
 use game_sdk::{gamerules, Action, ActionList, GameState};
 use rand::rngs::SmallRng;
 
 const C: f32 = 0.0;
-const C_BASE: f32 = 7000.;
-const C_FACTOR: f32 = 0.5;
 
 pub struct Node {
     pub n: f32,
@@ -26,17 +27,34 @@ impl Node {
         state: &mut GameState,
         al: &mut ActionList<Action>,
         rng: &mut SmallRng,
+        evaluator: &mut dyn Evaluator,
+        params: &MctsParameters,
     ) -> f32 {
         let delta;
-        let c_adjusted = C + C_FACTOR * ((1. + self.n + C_BASE) / C_BASE).ln();
+        let c_adjusted = C + params.c_factor * ((1. + self.n + params.c_base) / params.c_base).ln();
         if self.children.len() == 0 {
             if !gamerules::is_game_finished(state) {
                 gamerules::calculate_legal_moves(state, al);
                 self.children = Vec::with_capacity(al.size);
-                for i in 0..al.size {
-                    self.children.push(Edge::new(al[i]));
+                if params.selection == Selection::Puct {
+                    let priors = priors(state, al, evaluator, params);
+                    for (i, prior) in priors.into_iter().enumerate() {
+                        self.children.push(Edge::with_prior(al[i], prior));
+                    }
+                    // progressive widening selects among the first children
+                    self.children
+                        .sort_by(|a, b| b.prior.partial_cmp(&a.prior).unwrap());
+                } else {
+                    for i in 0..al.size {
+                        self.children.push(Edge::new(al[i]));
+                    }
                 }
-                delta = playout(state, al, rng);
+                delta = if params.evaluate_leaves {
+                    evaluator.prepare(state);
+                    1. - win_probability(evaluator.evaluate(state))
+                } else {
+                    playout(state, al, rng, &params.playout, evaluator)
+                };
             } else if self.n == 0. {
                 self.q = if let Some(winner) = gamerules::get_result(&state) {
                     if winner == state.color_to_move {
@@ -55,18 +73,46 @@ impl Node {
             self.backpropagate(delta);
             return 1. - delta;
         }
+        let best_edge = match params.selection {
+            Selection::Uct => {
+                let mut best_edge = 0;
+                let mut best_value = std::f32::NEG_INFINITY;
+                for (edge_idx, edge) in self.children.iter().enumerate() {
+                    let value = edge.get_uct_value(self.n, c_adjusted);
+                    if value >= best_value {
+                        best_edge = edge_idx;
+                        best_value = value;
+                    }
+                }
+                best_edge
+            }
+            Selection::Puct => self.select_puct(params),
+        };
+        delta = self.children[best_edge].iteration(state, al, rng, evaluator, params);
+        self.backpropagate(delta);
+        return 1. - delta;
+    }
+
+    /// Child with the highest PUCT value among the widened children. Unvisited children
+    /// are valued like the average result of this node for the player to move
+    fn select_puct(&self, params: &MctsParameters) -> usize {
+        let first_play_urgency = if self.n > 0. {
+            1. - self.q / self.n
+        } else {
+            0.5
+        };
+        let exploration = params.c_puct * self.n.sqrt();
+        let widened = params.widened_children(self.n).min(self.children.len());
         let mut best_edge = 0;
-        let mut best_value = std::f32::NEG_INFINITY;
-        for (edge_idx, edge) in self.children.iter().enumerate() {
-            let value = edge.get_uct_value(self.n, c_adjusted);
-            if value >= best_value {
+        let mut best_value = f32::NEG_INFINITY;
+        for (edge_idx, edge) in self.children[..widened].iter().enumerate() {
+            let value = edge.get_puct_value(first_play_urgency, exploration);
+            if value > best_value {
                 best_edge = edge_idx;
                 best_value = value;
             }
         }
-        delta = self.children[best_edge].iteration(state, al, rng);
-        self.backpropagate(delta);
-        return 1. - delta;
+        best_edge
     }
 
     pub fn backpropagate(&mut self, q: f32) {
@@ -112,13 +158,20 @@ impl Node {
 pub struct Edge {
     pub action: Action,
     pub node: Node,
+    /// Probability of the action being the best one, only used by `Selection::Puct`
+    pub prior: f32,
 }
 
 impl Edge {
     pub fn new(action: Action) -> Self {
+        Edge::with_prior(action, 0.)
+    }
+
+    pub fn with_prior(action: Action, prior: f32) -> Self {
         Edge {
             action,
             node: Node::empty(),
+            prior,
         }
     }
 
@@ -127,9 +180,11 @@ impl Edge {
         state: &mut GameState,
         al: &mut ActionList<Action>,
         rng: &mut SmallRng,
+        evaluator: &mut dyn Evaluator,
+        params: &MctsParameters,
     ) -> f32 {
         state.make_action(self.action);
-        self.node.iteration(state, al, rng)
+        self.node.iteration(state, al, rng, evaluator, params)
     }
 
     pub fn get_uct_value(&self, parent_n: f32, c: f32) -> f32 {
@@ -140,6 +195,16 @@ impl Edge {
         }
     }
 
+    /// `exploration` is the PUCT constant times the square root of the parent visits
+    pub fn get_puct_value(&self, first_play_urgency: f32, exploration: f32) -> f32 {
+        let q = if self.node.n > 0. {
+            self.node.q / self.node.n
+        } else {
+            first_play_urgency
+        };
+        q + exploration * self.prior / (1. + self.node.n)
+    }
+
     pub fn get_value(&self) -> f32 {
         if self.node.n > 0. {
             self.node.q / self.node.n
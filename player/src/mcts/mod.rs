@@ -1,37 +1,169 @@
 mod graph;
 mod playout;
+mod priors;
 
-use crate::search::Searcher;
-use crate::timecontrol::Timecontrol;
+pub use playout::PlayoutPolicy;
+
+use crate::evaluation::HandCrafted;
+use crate::evaluator::{evaluator_from_option, parameter_evaluator, Evaluator};
+use crate::options::{parse_value, Configurable};
+use crate::searchhandle::Interruptible;
+use crate::searchinfo::{InfoPrinter, ScoreType, SearchInfo, SearchListener};
+use crate::searchlimits::SearchLimits;
+use crate::timecontrol::{TimeManager, Timecontrol, DEFAULT_GAME_TIME};
+use game_sdk::gamerules::is_game_finished;
 use game_sdk::{Action, ActionList, ClientListener, GameState};
 use graph::Node;
 use rand::{rngs::SmallRng, SeedableRng};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Instant;
 
+/// Iterations between two checks of the limits when not looking at the clock
+const DETERMINISTIC_BATCH: usize = 1000;
+/// Share of the root visits the best move needs to dominate all others
+const DOMINANT_VISIT_SHARE: f32 = 0.9;
+/// Root visits needed before the visit share is trusted
+const DOMINANT_MIN_VISITS: f32 = 5000.;
+
+/// How the child that is searched next is chosen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Selection {
+    /// Upper confidence bound, every child is visited once before any is visited twice
+    Uct,
+    /// Weighs the exploration of every child by its prior and only considers
+    /// the children with the highest priors until the node has enough visits
+    Puct,
+}
+
+/// Where the priors of `Selection::Puct` come from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PriorSource {
+    /// The weights of the playout policy and pins
+    Policy,
+    /// A one ply search with the static evaluation
+    Evaluation,
+}
+
+/// Constants of the tree search that can be changed at runtime, e.g. to tune them
+#[derive(Clone, Debug, PartialEq)]
+pub struct MctsParameters {
+    /// Visits after which the exploration factor has grown by `c_factor * ln(2)`
+    pub c_base: f32,
+    pub c_factor: f32,
+    pub playout: PlayoutPolicy,
+    pub selection: Selection,
+    pub c_puct: f32,
+    pub prior_source: PriorSource,
+    /// A node with n visits selects among its `widening_factor * (n + 1)^widening_exponent`
+    /// children with the highest priors
+    pub widening_factor: f32,
+    pub widening_exponent: f32,
+    /// Scores new leaves with the evaluator of the search instead of playing them out
+    pub evaluate_leaves: bool,
+}
+
+impl MctsParameters {
+    /// Amount of children with the highest priors that `Selection::Puct` chooses from
+    pub fn widened_children(&self, visits: f32) -> usize {
+        (self.widening_factor * (visits + 1.).powf(self.widening_exponent))
+            .ceil()
+            .max(1.) as usize
+    }
+}
+
+impl Default for MctsParameters {
+    fn default() -> Self {
+        MctsParameters {
+            c_base: 7000.,
+            c_factor: 0.5,
+            playout: PlayoutPolicy::default(),
+            selection: Selection::Uct,
+            c_puct: 1.5,
+            prior_source: PriorSource::Policy,
+            widening_factor: 2.,
+            widening_exponent: 0.5,
+            evaluate_leaves: false,
+        }
+    }
+}
+
 pub struct MCTS {
     pub iterations_per_ms: f64,
     pub root: Node,
+    /// Time that is left, it is reset to `game_tc` at the start of every game
     pub tc: Timecontrol,
+    pub game_tc: Timecontrol,
+    pub limits: SearchLimits,
+    pub deterministic: bool,
+    /// Seed of the playout rng, a random one is used if None
+    pub seed: Option<u64>,
+    pub stop_signal: Arc<AtomicBool>,
+    pub listeners: Vec<Box<dyn SearchListener>>,
+    /// Scores the leaves if `params.evaluate_leaves` is set, and the playouts that reach their cutoff
+    pub evaluator: Box<dyn Evaluator>,
+    pub params: MctsParameters,
     initial_state: GameState,
 }
 
 impl MCTS {
     pub fn new() -> Self {
-        MCTS::with_tc(Timecontrol::MoveTime(1700))
+        MCTS::with_tc(Timecontrol::GameTime(DEFAULT_GAME_TIME))
     }
     pub fn with_tc(tc: Timecontrol) -> Self {
         MCTS {
             iterations_per_ms: 0.5,
             root: Node::empty(),
             tc,
+            game_tc: tc,
+            limits: SearchLimits::none(),
+            deterministic: false,
+            seed: None,
+            stop_signal: Arc::new(AtomicBool::new(false)),
+            listeners: vec![Box::new(InfoPrinter)],
+            evaluator: Box::new(HandCrafted::default()),
+            params: MctsParameters::default(),
             initial_state: GameState::new(),
         }
     }
 
+    /// MCTS that never looks at the clock, does not reuse its tree and seeds its rng with `seed`,
+    /// so the same position always results in the same move. Needs a node, playout or depth limit
+    pub fn deterministic(limits: SearchLimits, seed: u64) -> Self {
+        let mut res = MCTS::with_tc(Timecontrol::Infinite);
+        res.limits = limits;
+        res.deterministic = true;
+        res.seed = Some(seed);
+        res
+    }
+
+    /// Sets the timecontrol of this and of every following game
+    pub fn set_tc(&mut self, tc: Timecontrol) {
+        self.tc = tc;
+        self.game_tc = tc;
+    }
+
+    /// Resets all state that is carried over from one search to the next
+    pub fn clear(&mut self) {
+        self.root = Node::empty();
+        self.initial_state = GameState::new();
+        self.iterations_per_ms = 0.5;
+    }
+
     pub fn search_nodes(&mut self, state: &GameState, n: usize, rng: &mut SmallRng) {
         let mut al = ActionList::default();
         for _ in 0..n {
-            self.root.iteration(&mut state.clone(), &mut al, rng);
+            // the root needs to be expanded so there is a move to return
+            if self.root.n > 0. && self.stop_signal.load(Ordering::Relaxed) {
+                break;
+            }
+            self.root.iteration(
+                &mut state.clone(),
+                &mut al,
+                rng,
+                self.evaluator.as_mut(),
+                &self.params,
+            );
         }
     }
 
@@ -71,47 +203,110 @@ impl MCTS {
 
     pub fn search(&mut self, state: &GameState) {
         let start_time = Instant::now();
-        println!("Searching state w/ fen:{}", state.to_fen());
+        for listener in self.listeners.iter_mut() {
+            listener.on_search_start(state);
+        }
 
+        if self.deterministic {
+            // a depth limit also ends the search when every line of the principal variation
+            // ends the game, so it is enough on its own
+            assert!(
+                self.limits.nodes.is_some()
+                    || self.limits.playouts.is_some()
+                    || self.limits.depth.is_some(),
+                "A deterministic search needs a node, playout or depth limit"
+            );
+            self.clear();
+        }
         // tree reuse if possible
         self.set_root(state);
 
-        let mut rng = SmallRng::from_entropy();
+        let mut rng = match self.seed {
+            Some(seed) => SmallRng::seed_from_u64(seed),
+            None => SmallRng::from_entropy(),
+        };
         let mut samples = 0;
         let mut elapsed = 0;
         self.iterations_per_ms = 1.;
         let mut pv = ActionList::default();
+        let mut info = SearchInfo::default();
+        let mut time_manager = TimeManager::new(self.tc, state.ply);
         loop {
-            let time_left = self.tc.time_left(elapsed);
-            if time_left < 50 {
+            let time_left = if self.deterministic {
+                i64::MAX
+            } else {
+                time_manager.time_left(elapsed)
+            };
+            let limit_reached = self.stop_signal.load(Ordering::Relaxed)
+                || self.limits.playouts_reached(samples as u64)
+                || self.limits.nodes_reached(self.root.n as u64);
+            // the root needs to be expanded so there is a move to return
+            if time_left < 50 || (self.root.n > 0. && limit_reached) {
                 break;
             }
-            let to_search = ((time_left as f64 / 2.) * self.iterations_per_ms).max(1.) as usize;
+            let mut to_search = if self.deterministic {
+                DETERMINISTIC_BATCH
+            } else {
+                ((time_left as f64 / 2.) * self.iterations_per_ms).max(1.) as usize
+            };
+            if let Some(playouts) = self.limits.playouts {
+                to_search = to_search.min(playouts.saturating_sub(samples as u64).max(1) as usize);
+            }
+            if let Some(nodes) = self.limits.nodes {
+                to_search = to_search.min(nodes.saturating_sub(self.root.n as u64).max(1) as usize);
+            }
             self.search_nodes(state, to_search, &mut rng);
             samples += to_search;
             pv.clear();
             let pv_depth = self.root.build_pv(&mut state.clone(), &mut pv);
             elapsed = start_time.elapsed().as_millis() as u64;
             self.iterations_per_ms = samples as f64 / elapsed as f64;
-            let (score, pv_move) = self.root.best_action();
-            println!(
-                "info depth {} score {} bestmove {:?} nodes {} nps {:.2} time {} pv {}",
-                pv_depth,
-                (score * 100.) as isize,
-                pv_move,
-                self.root.n as usize,
-                self.iterations_per_ms * 1000.,
-                elapsed,
-                Searcher::format_pv(&pv)
-            );
+            let (score, best_action) = self.root.best_action();
+            if let Some(last_best_action) = info.best_action() {
+                time_manager.update(
+                    last_best_action != best_action,
+                    ((score * 100.) as i16).saturating_sub(info.score),
+                );
+            }
+            let best_visits = self
+                .root
+                .children
+                .iter()
+                .find(|edge| edge.action == best_action)
+                .map_or(0., |edge| edge.node.n);
+            if self.root.children.len() == 1
+                || self.root.n >= DOMINANT_MIN_VISITS
+                    && best_visits >= DOMINANT_VISIT_SHARE * self.root.n
+            {
+                time_manager.set_dominant();
+            }
+            info = SearchInfo {
+                depth: pv_depth,
+                seldepth: pv_depth,
+                score: (score * 100.) as i16,
+                score_type: Some(ScoreType::WinRate),
+                mate: None,
+                nodes: self.root.n as u64,
+                nps: self.iterations_per_ms * 1000.,
+                time: elapsed,
+                hashfull: None,
+                pv: SearchInfo::pv_from_actionlist(&pv),
+            };
+            for listener in self.listeners.iter_mut() {
+                listener.on_search_info(&info);
+            }
+            // lines that end the game can not get any longer, so they satisfy every depth limit
+            if self.limits.depth_reached(pv_depth)
+                || self.limits.depth.is_some() && pv_ends_game(state, &pv)
+            {
+                break;
+            }
         }
-        let (score, pv_move) = self.root.best_action();
-        println!(
-            "Finished search with move {:?} and score {}, pv: {}",
-            pv_move,
-            score as usize,
-            Searcher::format_pv(&pv)
-        );
+        let pv_move = self.best_action();
+        for listener in self.listeners.iter_mut() {
+            listener.on_search_finished(pv_move, &info);
+        }
+        self.tc = self.tc.spend(start_time.elapsed().as_millis() as u64);
     }
 
     pub fn best_action(&self) -> Action {
@@ -119,9 +314,375 @@ impl MCTS {
     }
 }
 
+/// Whether the game is finished after playing `pv` from `state`
+fn pv_ends_game(state: &GameState, pv: &ActionList<Action>) -> bool {
+    let mut state = state.clone();
+    for i in 0..pv.size {
+        state.make_action(pv[i]);
+    }
+    is_game_finished(&state)
+}
+
 impl ClientListener for MCTS {
     fn on_move_request(&mut self, state: &GameState) -> Action {
         self.search(state);
         self.best_action()
     }
 }
+
+impl Configurable for MCTS {
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "seed" => {
+                self.seed = Some(parse_value(name, value)?);
+                Ok(())
+            }
+            // the configured evaluator is kept for the playout cutoff
+            "evaluator" | "evalfile" if value.is_empty() || value == "playout" => {
+                self.params.evaluate_leaves = false;
+                Ok(())
+            }
+            "evaluator" | "evalfile" => {
+                self.evaluator = evaluator_from_option(value)?;
+                self.params.evaluate_leaves = true;
+                Ok(())
+            }
+            "evalparams" => {
+                self.evaluator = parameter_evaluator(value)?;
+                self.params.evaluate_leaves = true;
+                Ok(())
+            }
+            "cbase" => match parse_value(name, value)? {
+                c_base if c_base > 0. => {
+                    self.params.c_base = c_base;
+                    Ok(())
+                }
+                _ => Err("cbase has to be positive".to_owned()),
+            },
+            "cfactor" => {
+                self.params.c_factor = parse_value(name, value)?;
+                Ok(())
+            }
+            "playoutpolicy" => {
+                self.params.playout.weighted = match value {
+                    "uniform" => false,
+                    "weighted" => true,
+                    _ => return Err(format!("Unknown playout policy {}", value)),
+                };
+                Ok(())
+            }
+            "playoutattack" => {
+                self.params.playout.attack_weight = parse_weight(name, value)?;
+                Ok(())
+            }
+            "playoutrelease" => {
+                self.params.playout.release_weight = parse_weight(name, value)?;
+                Ok(())
+            }
+            "playoutownbee" => {
+                self.params.playout.own_bee_weight = parse_weight(name, value)?;
+                Ok(())
+            }
+            "playoutset" => {
+                self.params.playout.set_weight = parse_weight(name, value)?;
+                Ok(())
+            }
+            "playoutsetplies" => {
+                self.params.playout.set_plies = parse_value(name, value)?;
+                Ok(())
+            }
+            "selection" => {
+                self.params.selection = match value {
+                    "uct" => Selection::Uct,
+                    "puct" => Selection::Puct,
+                    _ => return Err(format!("Unknown selection {}", value)),
+                };
+                // the children of the current tree have no priors or other ones
+                self.clear();
+                Ok(())
+            }
+            "cpuct" => {
+                self.params.c_puct = parse_weight(name, value)?;
+                Ok(())
+            }
+            "priors" => {
+                self.params.prior_source = match value {
+                    "policy" => PriorSource::Policy,
+                    "evaluation" => PriorSource::Evaluation,
+                    _ => return Err(format!("Unknown prior source {}", value)),
+                };
+                // the children of the current tree have no priors or other ones
+                self.clear();
+                Ok(())
+            }
+            "wideningfactor" => match parse_weight(name, value)? {
+                factor if factor > 0. => {
+                    self.params.widening_factor = factor;
+                    Ok(())
+                }
+                _ => Err("wideningfactor has to be positive".to_owned()),
+            },
+            "wideningexponent" => {
+                self.params.widening_exponent = parse_weight(name, value)?;
+                Ok(())
+            }
+            // 0 plays every playout to the end of the game
+            "playoutcutoff" => {
+                self.params.playout.cutoff = match parse_value(name, value)? {
+                    0 => None,
+                    plies => Some(plies),
+                };
+                Ok(())
+            }
+            _ => Err(format!("Unknown option {}", name)),
+        }
+    }
+
+    fn new_game(&mut self) {
+        self.clear();
+        self.tc = self.game_tc;
+    }
+}
+
+/// Parses a weight or factor, which can not be negative
+fn parse_weight(name: &str, value: &str) -> Result<f32, String> {
+    match parse_value(name, value)? {
+        weight if weight >= 0. => Ok(weight),
+        _ => Err(format!("{} can not be negative", name)),
+    }
+}
+
+impl Interruptible for MCTS {
+    fn stop_signal(&self) -> Arc<AtomicBool> {
+        self.stop_signal.clone()
+    }
+
+    fn listeners(&mut self) -> &mut Vec<Box<dyn SearchListener>> {
+        &mut self.listeners
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MCTS;
+    use crate::evaluation::{EvaluationParameters, HandCrafted, DEFAULT_ARRAY};
+    use crate::evaluator::Evaluator;
+    use crate::options::Configurable;
+    use crate::searchlimits::SearchLimits;
+    use game_sdk::gamerules::{calculate_legal_moves, is_game_finished};
+    use game_sdk::{ActionList, GameState};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const FEN: &str = "20 RED 19807040637789456435240771584 576460752303423488 0 0 0 140737488355328 0 0 0 34359738368 4835703278458585418301440 140737488355328 2417851639229258349412352 8388608 2361183241434822606848 144115188109410304 0 288230376151711744 576460752303423488";
+
+    /// Counts its evaluations
+    struct CountingEvaluator(Arc<AtomicUsize>);
+
+    impl Evaluator for CountingEvaluator {
+        fn evaluate(&mut self, _game_state: &GameState) -> i16 {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            0
+        }
+
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+    }
+
+    #[test]
+    fn seeded_search_is_reproducible() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut mcts = MCTS::deterministic(SearchLimits::none().with_playouts(3000), 42);
+        mcts.listeners.clear();
+        mcts.search(&state);
+        let first = mcts.best_action();
+        let first_visits = mcts.root.n;
+        mcts.search(&state);
+        assert_eq!(mcts.best_action(), first);
+        assert_eq!(mcts.root.n, first_visits);
+    }
+
+    #[test]
+    fn evaluator_replaces_playouts() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut mcts = MCTS::deterministic(SearchLimits::none().with_playouts(500), 42);
+        mcts.listeners.clear();
+        mcts.set_option("evaluator", "handcrafted").unwrap();
+        assert!(mcts.params.evaluate_leaves);
+        assert!(mcts.set_option("evaluator", "/nonexistent").is_err());
+        mcts.set_option("evaluator", "playout").unwrap();
+        assert!(!mcts.params.evaluate_leaves);
+
+        let evaluated = Arc::new(AtomicUsize::new(0));
+        mcts.evaluator = Box::new(CountingEvaluator(evaluated.clone()));
+        // playouts without a cutoff never evaluate
+        mcts.search(&state);
+        assert_eq!(evaluated.load(Ordering::SeqCst), 0);
+        mcts.params.evaluate_leaves = true;
+        mcts.search(&state);
+        // every iteration evaluates the leaf it expands, unless the leaf ends the game
+        let evaluations = evaluated.load(Ordering::SeqCst);
+        assert!(evaluations > 0 && evaluations as f32 <= mcts.root.n);
+    }
+
+    #[test]
+    fn playout_policy_is_configurable() {
+        let mut mcts = MCTS::deterministic(SearchLimits::none().with_playouts(500), 42);
+        assert!(!mcts.params.playout.weighted);
+        mcts.set_option("playoutpolicy", "weighted").unwrap();
+        mcts.set_option("playoutattack", "8").unwrap();
+        mcts.set_option("playoutcutoff", "10").unwrap();
+        assert!(mcts.set_option("playoutrelease", "-1").is_err());
+        assert!(mcts.set_option("playoutpolicy", "greedy").is_err());
+        let policy = &mcts.params.playout;
+        assert!(policy.weighted);
+        assert_eq!(policy.attack_weight, 8.);
+        assert_eq!(policy.cutoff, Some(10));
+        mcts.set_option("playoutcutoff", "0").unwrap();
+        assert_eq!(mcts.params.playout.cutoff, None);
+    }
+
+    #[test]
+    fn puct_only_visits_widened_children() {
+        let state = GameState::from_fen(FEN.to_owned());
+        // few enough visits that not all 29 moves are widened yet
+        let mut mcts = MCTS::deterministic(SearchLimits::none().with_playouts(100), 42);
+        mcts.listeners.clear();
+        mcts.set_option("selection", "puct").unwrap();
+        for source in ["policy", "evaluation"].iter() {
+            mcts.set_option("priors", source).unwrap();
+            mcts.search(&state);
+            let children = &mcts.root.children;
+            let prior_sum: f32 = children.iter().map(|edge| edge.prior).sum();
+            assert!((prior_sum - 1.).abs() < 1e-3);
+            assert!(children.windows(2).all(|w| w[0].prior >= w[1].prior));
+            let widened = mcts.params.widened_children(mcts.root.n);
+            assert!(widened < children.len());
+            assert!(children[widened..].iter().all(|edge| edge.node.n == 0.));
+            assert!(children[..widened].iter().any(|edge| edge.node.n > 0.));
+        }
+    }
+
+    #[test]
+    fn evaluation_priors_use_the_configured_evaluator() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut mcts = MCTS::deterministic(SearchLimits::none().with_playouts(1), 42);
+        mcts.listeners.clear();
+        mcts.set_option("selection", "puct").unwrap();
+        mcts.set_option("priors", "evaluation").unwrap();
+        let evaluated = Arc::new(AtomicUsize::new(0));
+        mcts.evaluator = Box::new(CountingEvaluator(evaluated.clone()));
+        mcts.search(&state);
+        let mut al = ActionList::default();
+        calculate_legal_moves(&state, &mut al);
+        // the root is expanded with a prior for every move, playouts do not evaluate
+        assert_eq!(evaluated.load(Ordering::SeqCst), al.size);
+        // equal scores give equal priors
+        let children = &mcts.root.children;
+        assert!(children
+            .iter()
+            .all(|edge| (edge.prior - 1. / al.size as f32).abs() < 1e-6));
+    }
+
+    #[test]
+    fn search_stops_at_its_limits() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut mcts = MCTS::deterministic(SearchLimits::none().with_playouts(300), 42);
+        mcts.listeners.clear();
+        mcts.search(&state);
+        assert_eq!(mcts.root.n, 300.);
+        mcts.limits = SearchLimits::none().with_nodes(500);
+        mcts.search(&state);
+        assert_eq!(mcts.root.n, 500.);
+        let mut pv = ActionList::default();
+        mcts.limits = SearchLimits::none().with_depth(2);
+        mcts.search(&state);
+        assert!(mcts.root.build_pv(&mut state.clone(), &mut pv) >= 2);
+    }
+
+    #[test]
+    fn evalparams_option_evaluates_leaves_with_the_parameters() {
+        let path = std::env::temp_dir().join(format!("mcts-params-{}.txt", std::process::id()));
+        let mut params = DEFAULT_ARRAY;
+        params[0] *= 3.;
+        let params = EvaluationParameters::from_array(params);
+        params.save(&path).unwrap();
+        let mut mcts = MCTS::deterministic(SearchLimits::none().with_playouts(100), 42);
+        mcts.set_option("evalparams", path.to_str().unwrap())
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(mcts.params.evaluate_leaves);
+
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut expected = HandCrafted::with_parameters(params);
+        mcts.evaluator.prepare(&state);
+        expected.prepare(&state);
+        assert_eq!(mcts.evaluator.evaluate(&state), expected.evaluate(&state));
+    }
+
+    #[test]
+    fn exploration_is_configurable() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut mcts = MCTS::deterministic(SearchLimits::none().with_playouts(2000), 42);
+        mcts.listeners.clear();
+        assert!(mcts.set_option("cbase", "0").is_err());
+        let mut most_visits = |c_factor: &str| {
+            mcts.set_option("cfactor", c_factor).unwrap();
+            mcts.search(&state);
+            mcts.root
+                .children
+                .iter()
+                .map(|edge| edge.node.n)
+                .fold(0., f32::max)
+        };
+        // without exploration the search sticks to the best move after visiting each once
+        let greedy = most_visits("0");
+        let exploring = most_visits("4");
+        assert!(greedy > exploring);
+    }
+
+    /// The position before the last move of a game that always plays the first legal move
+    fn last_position_of_a_game() -> GameState {
+        let mut state = GameState::from_fen(FEN.to_owned());
+        let mut al = ActionList::default();
+        loop {
+            calculate_legal_moves(&state, &mut al);
+            let mut next = state.clone();
+            next.make_action(al[0]);
+            if is_game_finished(&next) {
+                return state;
+            }
+            state = next;
+        }
+    }
+
+    #[test]
+    fn depth_limit_stops_at_the_end_of_the_game() {
+        let state = last_position_of_a_game();
+        let mut al = ActionList::default();
+        calculate_legal_moves(&state, &mut al);
+        let mut mcts = MCTS::deterministic(SearchLimits::none().with_depth(5), 1);
+        mcts.listeners.clear();
+        mcts.search(&state);
+        assert!(al.find_action(mcts.best_action()).is_some());
+    }
+
+    #[test]
+    fn deterministic_depth_limit_is_reproducible() {
+        let mut mcts = MCTS::deterministic(SearchLimits::none().with_depth(3), 7);
+        mcts.listeners.clear();
+        for state in [
+            GameState::from_fen(FEN.to_owned()),
+            last_position_of_a_game(),
+        ]
+        .iter()
+        {
+            mcts.search(state);
+            let (first, first_visits) = (mcts.best_action(), mcts.root.n);
+            mcts.search(state);
+            assert_eq!(mcts.best_action(), first);
+            assert_eq!(mcts.root.n, first_visits);
+        }
+    }
+}
@@ -1,14 +1,116 @@
+use crate::evaluator::{win_probability, Evaluator};
+use game_sdk::bitboard::get_neighbours;
 use game_sdk::{actionlist::ActionList, gamerules, Action, Color, GameState, PieceType};
-use rand::{rngs::SmallRng, RngCore};
+use rand::{rngs::SmallRng, Rng, RngCore};
 
-pub fn playout(initial: &GameState, al: &mut ActionList<Action>, rng: &mut SmallRng) -> f32 {
-    let mut state = initial.clone();
+/// How moves are picked during a playout
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayoutPolicy {
+    /// Picks moves with the weights below instead of uniformly
+    pub weighted: bool,
+    /// Weight of moves that add a neighbour to the enemy bee
+    pub attack_weight: f32,
+    /// Weight of moves that take a piece away from the enemy bee
+    pub release_weight: f32,
+    /// Weight of moves that add a neighbour to the own bee
+    pub own_bee_weight: f32,
+    /// Weight of set moves in the first `set_plies` plies of the game
+    pub set_weight: f32,
+    pub set_plies: u8,
+    /// Plies after which a playout stops and the static evaluation decides its result
+    pub cutoff: Option<usize>,
+}
+
+impl Default for PlayoutPolicy {
+    fn default() -> Self {
+        PlayoutPolicy {
+            weighted: false,
+            attack_weight: 4.,
+            release_weight: 0.25,
+            own_bee_weight: 0.5,
+            set_weight: 2.,
+            set_plies: 20,
+            cutoff: None,
+        }
+    }
+}
 
+impl PlayoutPolicy {
+    /// Relative probability of playing `action` in `state`, every move starts at 1
+    pub fn weight(&self, state: &GameState, action: Action) -> f32 {
+        let bee = PieceType::BEE as usize;
+        let enemy_bee = get_neighbours(state.pieces[bee][state.color_to_move.swap() as usize]);
+        let own_bee = get_neighbours(state.pieces[bee][state.color_to_move as usize]);
+        let (piece_type, from, to) = match action {
+            Action::SkipMove => return 1.,
+            Action::SetMove(piece_type, to) => (piece_type, 0, 1u128 << to),
+            Action::DragMove(piece_type, from, to) => (piece_type, 1u128 << from, 1u128 << to),
+        };
+        let mut weight = 1.;
+        if from == 0 && state.ply < self.set_plies {
+            weight *= self.set_weight;
+        }
+        if to & enemy_bee > 0 && from & enemy_bee == 0 {
+            weight *= self.attack_weight;
+        } else if from & enemy_bee > 0 && to & enemy_bee == 0 {
+            weight *= self.release_weight;
+        }
+        if piece_type != PieceType::BEE && to & own_bee > 0 && from & own_bee == 0 {
+            weight *= self.own_bee_weight;
+        }
+        weight
+    }
+
+    fn pick(
+        &self,
+        state: &GameState,
+        al: &ActionList<Action>,
+        weights: &mut Vec<f32>,
+        rng: &mut SmallRng,
+    ) -> Action {
+        if !self.weighted {
+            return al[rng.next_u64() as usize % al.size];
+        }
+        weights.clear();
+        weights.extend((0..al.size).map(|i| self.weight(state, al[i])));
+        let mut pick = rng.gen::<f32>() * weights.iter().sum::<f32>();
+        for (i, weight) in weights.iter().enumerate() {
+            if pick < *weight {
+                return al[i];
+            }
+            pick -= weight;
+        }
+        al[al.size - 1]
+    }
+}
+
+/// Result of a playout from `initial` for the player that moved last. `evaluator` scores
+/// the playouts that reach the cutoff of `policy`
+pub fn playout(
+    initial: &GameState,
+    al: &mut ActionList<Action>,
+    rng: &mut SmallRng,
+    policy: &PlayoutPolicy,
+    evaluator: &mut dyn Evaluator,
+) -> f32 {
+    let mut state = initial.clone();
+    let mut weights = Vec::new();
+    let mut plies = 0;
     while !gamerules::is_game_finished(&state) {
+        if policy.cutoff.is_some_and(|cutoff| plies >= cutoff) {
+            evaluator.prepare(&state);
+            let score = evaluator.evaluate(&state);
+            let score = if state.color_to_move == initial.color_to_move {
+                score
+            } else {
+                -score
+            };
+            return 1. - win_probability(score);
+        }
         gamerules::calculate_legal_moves(&state, al);
-        let rand = rng.next_u64() as usize % al.size;
-        let action = al[rand];
+        let action = policy.pick(&state, al, &mut weights, rng);
         state.make_action(action);
+        plies += 1;
     }
     get_score(&state, initial.color_to_move)
 }
@@ -43,3 +145,72 @@ fn get_score(state: &GameState, color: Color) -> f32 {
     // else other is bigger
     1. - rate
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{playout, PlayoutPolicy};
+    use crate::evaluator::Evaluator;
+    use game_sdk::gamerules::calculate_legal_moves;
+    use game_sdk::{ActionList, GameState};
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    const FEN: &str = "20 RED 19807040637789456435240771584 576460752303423488 0 0 0 140737488355328 0 0 0 34359738368 4835703278458585418301440 140737488355328 2417851639229258349412352 8388608 2361183241434822606848 144115188109410304 0 288230376151711744 576460752303423488";
+
+    /// Remembers the plies of the states it evaluates
+    struct PlyRecorder(Vec<u8>);
+
+    impl Evaluator for PlyRecorder {
+        fn evaluate(&mut self, game_state: &GameState) -> i16 {
+            self.0.push(game_state.ply);
+            0
+        }
+
+        fn name(&self) -> &'static str {
+            "plyrecorder"
+        }
+    }
+
+    #[test]
+    fn playouts_stop_at_the_cutoff() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut al = ActionList::default();
+        let mut rng = SmallRng::seed_from_u64(42);
+        let mut recorder = PlyRecorder(Vec::new());
+        let mut policy = PlayoutPolicy::default();
+        for _ in 0..20 {
+            playout(&state, &mut al, &mut rng, &policy, &mut recorder);
+        }
+        assert!(recorder.0.is_empty());
+        policy.cutoff = Some(10);
+        for _ in 0..20 {
+            playout(&state, &mut al, &mut rng, &policy, &mut recorder);
+        }
+        assert!(!recorder.0.is_empty());
+        assert!(recorder.0.iter().all(|&ply| ply == state.ply + 10));
+    }
+
+    #[test]
+    fn weighted_policy_picks_moves_by_weight() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut al = ActionList::default();
+        calculate_legal_moves(&state, &mut al);
+        let policy = PlayoutPolicy {
+            weighted: true,
+            ..PlayoutPolicy::default()
+        };
+        let weights: Vec<f32> = (0..al.size).map(|i| policy.weight(&state, al[i])).collect();
+        let sum: f32 = weights.iter().sum();
+        assert!(weights.iter().any(|&weight| weight != weights[0]));
+        let mut rng = SmallRng::seed_from_u64(42);
+        let mut counts = vec![0; al.size];
+        let draws = 50000;
+        for _ in 0..draws {
+            let action = policy.pick(&state, &al, &mut Vec::new(), &mut rng);
+            counts[al.find_action(action).unwrap()] += 1;
+        }
+        for (count, weight) in counts.iter().zip(weights.iter()) {
+            assert!((*count as f32 / draws as f32 - weight / sum).abs() < 0.01);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use super::{MctsParameters, PriorSource};
+use crate::evaluator::Evaluator;
+use game_sdk::{Action, ActionList, GameState};
+
+/// Prior of a move that leaves a single enemy piece as its only neighbour, which pins that piece
+pub const ENEMY_PIN_FACTOR: f32 = 2.;
+/// Prior of a move that pins an own piece
+pub const OWN_PIN_FACTOR: f32 = 0.5;
+/// Score difference in evaluation units that makes a move e times as likely
+pub const EVALUATION_TEMPERATURE: f32 = 100.;
+
+/// Probability of every move in `al` being the best one, they add up to 1.
+/// `PriorSource::Evaluation` scores the moves with `evaluator`
+pub fn priors(
+    state: &mut GameState,
+    al: &ActionList<Action>,
+    evaluator: &mut dyn Evaluator,
+    params: &MctsParameters,
+) -> Vec<f32> {
+    let mut res: Vec<f32> = match params.prior_source {
+        PriorSource::Policy => (0..al.size)
+            .map(|i| {
+                let pin_factor = match state.get_pin_info(al[i]) {
+                    Some((_, color, _)) if color == state.color_to_move => OWN_PIN_FACTOR,
+                    Some(_) => ENEMY_PIN_FACTOR,
+                    None => 1.,
+                };
+                params.playout.weight(state, al[i]) * pin_factor
+            })
+            .collect(),
+        PriorSource::Evaluation => {
+            // the hand-crafted evaluator keeps its obstacle info while the obstacles stay the same
+            evaluator.prepare(state);
+            let scores: Vec<f32> = (0..al.size)
+                .map(|i| {
+                    evaluator.make_action(state, al[i]);
+                    state.make_action(al[i]);
+                    let score = -evaluator.evaluate(state) as f32;
+                    state.unmake_action(al[i]);
+                    evaluator.unmake_action();
+                    score
+                })
+                .collect();
+            let best = scores.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            scores
+                .iter()
+                .map(|score| ((score - best) / EVALUATION_TEMPERATURE).exp())
+                .collect()
+        }
+    };
+    let sum: f32 = res.iter().sum();
+    for prior in res.iter_mut() {
+        *prior = if sum > 0. {
+            *prior / sum
+        } else {
+            1. / al.size as f32
+        };
+    }
+    res
+}
@@ -8,6 +8,10 @@ use game_sdk::{Action, ActionList, Color, GameState, PieceType};
 
 pub const ATTACKER_VALUE: [f64; 5] = [5., 1., 4., 3., 2.];
 pub const TARGET_VALUE: [f64; 5] = [500., 400., 300., 100., 200.];
+/// Bonus for quiet moves next to the enemy bee, per neighbour of it blocked by obstacles or the edge
+pub const ATTACK_BLOCKED_BEE_BONUS: f64 = 0.25;
+/// Bonus for moving or setting the own bee, per neighbour of its destination that can still be freed
+pub const BEE_FREE_SQUARE_BONUS: f64 = 0.1;
 pub const STAGES: [MoveOrderingStage; 7] = [
     PVMove,
     TTMove,
@@ -197,13 +201,33 @@ impl MoveOrderer {
                 }
             }
             QuietInitialization => {
+                let info = &searcher.obstacle_info;
+                let enemy_bee = game_state.pieces[PieceType::BEE as usize]
+                    [game_state.color_to_move.swap() as usize]
+                    .trailing_zeros() as usize;
+                let (attack_squares, attack_bonus) = if enemy_bee <= 120 {
+                    (
+                        info.free_neighbours[enemy_bee],
+                        ATTACK_BLOCKED_BEE_BONUS * info.blocked_neighbours[enemy_bee] as f64,
+                    )
+                } else {
+                    (0, 0.)
+                };
                 for i in 0..searcher.als[current_depth].size {
                     if self.score_list[i].is_none() {
-                        let (from, to) = match searcher.als[current_depth][i] {
-                            Action::SkipMove => (121, 121),
-                            Action::DragMove(_, from, to) => (from, to),
-                            Action::SetMove(_, to) => (121, to),
+                        let (piece_type, from, to) = match searcher.als[current_depth][i] {
+                            Action::SkipMove => (None, 121, 121),
+                            Action::DragMove(piece_type, from, to) => (Some(piece_type), from, to),
+                            Action::SetMove(piece_type, to) => (Some(piece_type), 121, to),
                         };
+                        let mut obstacle_bonus = 0.;
+                        if to <= 120 && attack_squares & (1u128 << to) > 0 {
+                            obstacle_bonus += attack_bonus;
+                        }
+                        if piece_type == Some(PieceType::BEE) {
+                            obstacle_bonus += BEE_FREE_SQUARE_BONUS
+                                * (6 - info.blocked_neighbours[to as usize]) as f64;
+                        }
                         self.score_list.overwrite(
                             i,
                             Some(
@@ -211,7 +235,8 @@ impl MoveOrderer {
                                     [to as usize] as f64
                                     / searcher.bf_score[game_state.color_to_move as usize]
                                         [from as usize][to as usize]
-                                        as f64,
+                                        as f64
+                                    + obstacle_bonus,
                             ),
                         )
                     }
@@ -0,0 +1,426 @@
+use crate::evaluator::Evaluator;
+use game_sdk::{Action, Color, GameState, PieceType};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+pub const NNUE_MAGIC: [u8; 8] = *b"HIVENNUE";
+/// Has to be increased whenever the features or the file layout change
+pub const NNUE_VERSION: u32 = 1;
+pub const NUM_SQUARES: usize = 121;
+/// The five piece types on the ground, followed by the four levels of beetle stacks
+pub const NUM_LAYERS: usize = 5 + 4;
+/// One feature per piece of a color that can be undeployed, set while at least that many are in hand
+pub const NUM_HAND_FEATURES: usize = 11;
+/// Indexed by piece type
+pub const MAX_UNDEPLOYED: [u8; 5] = [1, 3, 2, 2, 3];
+const HAND_OFFSETS: [usize; 5] = [0, 1, 4, 6, 8];
+const HAND_START: usize = 2 * NUM_LAYERS * NUM_SQUARES;
+const OBSTACLE_START: usize = HAND_START + 2 * NUM_HAND_FEATURES;
+pub const NUM_FEATURES: usize = OBSTACLE_START + NUM_SQUARES;
+pub const DEFAULT_HIDDEN_SIZE: usize = 64;
+/// Activations are clipped to [0, 1], which is [0, QA] after quantization
+pub const QA: i32 = 255;
+pub const QB: i32 = 64;
+/// An output of 1 in the float network is an evaluation of `EVAL_SCALE`
+pub const EVAL_SCALE: i32 = 400;
+const MAX_EVALUATION: i32 = 10000;
+
+// Features are relative to a perspective, 0 being the pieces of the perspective's color.
+// Both perspectives share the weights, so the network does not care which color it plays
+#[inline(always)]
+fn relative_color(perspective: Color, color: usize) -> usize {
+    (color != perspective as usize) as usize
+}
+
+/// Piece of `color` on `square`. Layers 0 to 4 are the piece types, 5 to 8 the beetle stack levels
+pub fn square_feature(perspective: Color, color: usize, layer: usize, square: usize) -> usize {
+    (relative_color(perspective, color) * NUM_LAYERS + layer) * NUM_SQUARES + square
+}
+
+/// Set while `color` has at least `count` pieces of `piece_type` in hand
+pub fn hand_feature(perspective: Color, color: usize, piece_type: usize, count: u8) -> usize {
+    debug_assert!(count > 0 && count <= MAX_UNDEPLOYED[piece_type]);
+    HAND_START
+        + relative_color(perspective, color) * NUM_HAND_FEATURES
+        + HAND_OFFSETS[piece_type]
+        + count as usize
+        - 1
+}
+
+pub fn obstacle_feature(square: usize) -> usize {
+    OBSTACLE_START + square
+}
+
+pub fn active_features(state: &GameState, perspective: Color) -> Vec<usize> {
+    let mut res = Vec::with_capacity(48);
+    let mut push_squares = |mut bitboard: u128, feature: &dyn Fn(usize) -> usize| {
+        while bitboard > 0 {
+            let square = bitboard.trailing_zeros() as usize;
+            bitboard ^= 1u128 << square;
+            res.push(feature(square));
+        }
+    };
+    for color in 0..2 {
+        for piece_type in 0..5 {
+            push_squares(state.pieces[piece_type][color], &|square| {
+                square_feature(perspective, color, piece_type, square)
+            });
+        }
+        for level in 0..4 {
+            push_squares(state.beetle_stack[level][color], &|square| {
+                square_feature(perspective, color, 5 + level, square)
+            });
+        }
+    }
+    push_squares(state.obstacles, &obstacle_feature);
+    for color in 0..2 {
+        for piece_type in 0..5 {
+            for count in 1..=state.undeployed_counts[color][piece_type] {
+                res.push(hand_feature(perspective, color, piece_type, count));
+            }
+        }
+    }
+    res
+}
+
+/// Feature that is removed and feature that is added by `action` in `state`
+pub fn changed_features(
+    state: &GameState,
+    action: Action,
+    perspective: Color,
+) -> Option<(usize, usize)> {
+    let color = state.color_to_move as usize;
+    match action {
+        Action::SkipMove => None,
+        Action::SetMove(piece_type, to) => {
+            let piece_type = piece_type as usize;
+            Some((
+                hand_feature(
+                    perspective,
+                    color,
+                    piece_type,
+                    state.undeployed_counts[color][piece_type],
+                ),
+                square_feature(perspective, color, piece_type, to as usize),
+            ))
+        }
+        Action::DragMove(PieceType::BEETLE, from, to) => {
+            let stack_level = |bit: u128| {
+                (0..4).find(|&level| {
+                    (state.beetle_stack[level][0] | state.beetle_stack[level][1]) & bit == 0
+                })
+            };
+            let from_layer = match stack_level(1u128 << from) {
+                Some(0) => PieceType::BEETLE as usize,
+                Some(level) => 5 + level - 1,
+                None => 5 + 3,
+            };
+            let to_layer = if state.occupied() & (1u128 << to) > 0 {
+                5 + stack_level(1u128 << to).expect("Beetle stack is full")
+            } else {
+                PieceType::BEETLE as usize
+            };
+            Some((
+                square_feature(perspective, color, from_layer, from as usize),
+                square_feature(perspective, color, to_layer, to as usize),
+            ))
+        }
+        Action::DragMove(piece_type, from, to) => Some((
+            square_feature(perspective, color, piece_type as usize, from as usize),
+            square_feature(perspective, color, piece_type as usize, to as usize),
+        )),
+    }
+}
+
+/// Quantized network with one hidden layer per perspective, which is computed incrementally
+pub struct Network {
+    pub hidden_size: usize,
+    /// `NUM_FEATURES` rows of `hidden_size` weights, scaled by `QA`
+    pub feature_weights: Vec<i16>,
+    pub feature_bias: Vec<i16>,
+    /// Weights of the side to move's hidden layer followed by the ones of the other side, scaled by `QB`
+    pub output_weights: Vec<i16>,
+    /// Scaled by `QA * QB`
+    pub output_bias: i32,
+}
+
+impl Network {
+    pub fn zeroed(hidden_size: usize) -> Network {
+        Network {
+            hidden_size,
+            feature_weights: vec![0; NUM_FEATURES * hidden_size],
+            feature_bias: vec![0; hidden_size],
+            output_weights: vec![0; 2 * hidden_size],
+            output_bias: 0,
+        }
+    }
+
+    /// Reads a network written by `save`. Networks with other features are an error
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Network> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0u8; 8 + 4 + 4 + 4];
+        reader.read_exact(&mut header)?;
+        if header[..8] != NNUE_MAGIC {
+            return Err(invalid_data("not a network file".to_owned()));
+        }
+        let version = read_u32(&header[8..12]);
+        if version != NNUE_VERSION {
+            return Err(invalid_data(format!(
+                "network has version {}, expected {}",
+                version, NNUE_VERSION
+            )));
+        }
+        let num_features = read_u32(&header[12..16]) as usize;
+        if num_features != NUM_FEATURES {
+            return Err(invalid_data(format!(
+                "network has {} features, expected {}",
+                num_features, NUM_FEATURES
+            )));
+        }
+        let mut network = Network::zeroed(read_u32(&header[16..20]) as usize);
+        read_i16s(&mut reader, &mut network.feature_weights)?;
+        read_i16s(&mut reader, &mut network.feature_bias)?;
+        read_i16s(&mut reader, &mut network.output_weights)?;
+        let mut bias = [0u8; 4];
+        reader.read_exact(&mut bias)?;
+        network.output_bias = i32::from_le_bytes(bias);
+        Ok(network)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&NNUE_MAGIC)?;
+        writer.write_all(&NNUE_VERSION.to_le_bytes())?;
+        writer.write_all(&(NUM_FEATURES as u32).to_le_bytes())?;
+        writer.write_all(&(self.hidden_size as u32).to_le_bytes())?;
+        for weights in [
+            &self.feature_weights,
+            &self.feature_bias,
+            &self.output_weights,
+        ]
+        .iter()
+        {
+            for weight in weights.iter() {
+                writer.write_all(&weight.to_le_bytes())?;
+            }
+        }
+        writer.write_all(&self.output_bias.to_le_bytes())?;
+        writer.flush()
+    }
+
+    pub fn refresh(&self, state: &GameState, accumulator: &mut Accumulator) {
+        for perspective in [Color::RED, Color::BLUE].iter() {
+            let values = &mut accumulator.values[*perspective as usize];
+            values.clear();
+            values.extend(self.feature_bias.iter().map(|&bias| bias as i32));
+            for feature in active_features(state, *perspective) {
+                self.add_feature(values, feature);
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn add_feature(&self, values: &mut [i32], feature: usize) {
+        let weights = &self.feature_weights[feature * self.hidden_size..][..self.hidden_size];
+        for (value, weight) in values.iter_mut().zip(weights.iter()) {
+            *value += *weight as i32;
+        }
+    }
+
+    #[inline(always)]
+    fn remove_feature(&self, values: &mut [i32], feature: usize) {
+        let weights = &self.feature_weights[feature * self.hidden_size..][..self.hidden_size];
+        for (value, weight) in values.iter_mut().zip(weights.iter()) {
+            *value -= *weight as i32;
+        }
+    }
+
+    /// Evaluation of the position of `accumulator` from the view of the player to move
+    pub fn evaluate(&self, accumulator: &Accumulator, color_to_move: Color) -> i16 {
+        let (own, other) = self.output_weights.split_at(self.hidden_size);
+        let mut sum = self.output_bias;
+        for (values, weights) in [
+            (&accumulator.values[color_to_move as usize], own),
+            (&accumulator.values[color_to_move.swap() as usize], other),
+        ]
+        .iter()
+        {
+            for (value, weight) in values.iter().zip(weights.iter()) {
+                sum += (*value).clamp(0, QA) * *weight as i32;
+            }
+        }
+        let evaluation = (sum as i64 * EVAL_SCALE as i64 / (QA * QB) as i64) as i32;
+        evaluation.clamp(-MAX_EVALUATION, MAX_EVALUATION) as i16
+    }
+}
+
+/// Hidden layer before the activation, indexed by perspective. The values are wider than
+/// the weights, as a sum of many large weights does not fit into an i16
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct Accumulator {
+    pub values: [Vec<i32>; 2],
+}
+
+/// A network and one accumulator per ply of the line that is searched at the moment
+pub struct NnueState {
+    pub network: Arc<Network>,
+    stack: Vec<Accumulator>,
+    top: usize,
+}
+
+impl NnueState {
+    pub fn new(network: Arc<Network>) -> NnueState {
+        NnueState {
+            network,
+            stack: vec![Accumulator::default()],
+            top: 0,
+        }
+    }
+
+    /// Computes the accumulator of `state` from scratch, has to be called before every search
+    pub fn refresh(&mut self, state: &GameState) {
+        self.top = 0;
+        self.network.refresh(state, &mut self.stack[0]);
+    }
+
+    pub fn accumulator(&self) -> &Accumulator {
+        &self.stack[self.top]
+    }
+}
+
+impl Evaluator for NnueState {
+    /// The state has to be the one the accumulator belongs to
+    fn evaluate(&mut self, game_state: &GameState) -> i16 {
+        self.network
+            .evaluate(self.accumulator(), game_state.color_to_move)
+    }
+
+    fn prepare(&mut self, game_state: &GameState) {
+        self.refresh(game_state);
+    }
+
+    fn make_action(&mut self, state: &GameState, action: Action) {
+        if self.top + 1 == self.stack.len() {
+            self.stack.push(self.stack[self.top].clone());
+        } else {
+            let (lower, upper) = self.stack.split_at_mut(self.top + 1);
+            upper[0].clone_from(&lower[self.top]);
+        }
+        self.top += 1;
+        for perspective in [Color::RED, Color::BLUE].iter() {
+            if let Some((removed, added)) = changed_features(state, action, *perspective) {
+                let values = &mut self.stack[self.top].values[*perspective as usize];
+                self.network.remove_feature(values, removed);
+                self.network.add_feature(values, added);
+            }
+        }
+    }
+
+    fn unmake_action(&mut self) {
+        debug_assert!(self.top > 0);
+        self.top -= 1;
+    }
+
+    fn name(&self) -> &'static str {
+        "nnue"
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut res = [0u8; 4];
+    res.copy_from_slice(bytes);
+    u32::from_le_bytes(res)
+}
+
+fn read_i16s<R: Read>(reader: &mut R, values: &mut [i16]) -> io::Result<()> {
+    let mut bytes = vec![0u8; 2 * values.len()];
+    reader.read_exact(&mut bytes)?;
+    for (value, chunk) in values.iter_mut().zip(bytes.chunks_exact(2)) {
+        *value = i16::from_le_bytes([chunk[0], chunk[1]]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Accumulator, Network, NnueState, EVAL_SCALE, NUM_FEATURES, QA, QB};
+    use crate::evaluator::Evaluator;
+    use game_sdk::gamerules::{calculate_legal_moves, is_game_finished};
+    use game_sdk::{Action, ActionList, GameState, PieceType};
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+    use std::sync::Arc;
+
+    #[test]
+    fn incremental_updates_match_refresh() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut network = Network::zeroed(8);
+        for weight in network.feature_weights.iter_mut() {
+            *weight = rng.gen_range(-64, 64);
+        }
+        for weight in network.output_weights.iter_mut() {
+            *weight = rng.gen_range(-64, 64);
+        }
+        assert_eq!(network.feature_weights.len(), NUM_FEATURES * 8);
+        let path = std::env::temp_dir().join(format!("nnue-{}.bin", std::process::id()));
+        network.save(&path).unwrap();
+        let loaded = Network::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.feature_weights, network.feature_weights);
+
+        let mut state = GameState::new();
+        state.obstacles = (1u128 << 5) | (1u128 << 49) | (1u128 << 107);
+        let mut nnue = NnueState::new(Arc::new(network));
+        nnue.refresh(&state);
+        let mut actions = Vec::new();
+        let mut al = ActionList::default();
+        while !is_game_finished(&state) {
+            calculate_legal_moves(&state, &mut al);
+            // prefer beetle moves, so stacks are built
+            let action = (0..al.size)
+                .map(|i| al[i])
+                .find(|action| match action {
+                    Action::DragMove(PieceType::BEETLE, _, _) => rng.gen_bool(0.5),
+                    _ => false,
+                })
+                .unwrap_or_else(|| al[rng.gen_range(0, al.size)]);
+            nnue.make_action(&state, action);
+            state.make_action(action);
+            let mut expected = Accumulator::default();
+            nnue.network.refresh(&state, &mut expected);
+            assert_eq!(*nnue.accumulator(), expected);
+            actions.push(action);
+        }
+        while let Some(action) = actions.pop() {
+            state.unmake_action(action);
+            nnue.unmake_action();
+            let mut expected = Accumulator::default();
+            nnue.network.refresh(&state, &mut expected);
+            assert_eq!(*nnue.accumulator(), expected);
+        }
+    }
+
+    #[test]
+    fn large_weights_do_not_overflow() {
+        let mut network = Network::zeroed(1);
+        for weight in network.feature_weights.iter_mut() {
+            *weight = i16::MAX;
+        }
+        network.feature_bias[0] = i16::MAX;
+        network.output_weights = vec![1, 0];
+        let mut state = GameState::new();
+        state.obstacles = (1u128 << 5) | (1u128 << 49) | (1u128 << 107);
+        let mut nnue = NnueState::new(Arc::new(network));
+        nnue.refresh(&state);
+        // bias, 3 obstacles and 2 * 11 pieces in hand
+        assert_eq!(nnue.accumulator().values[0][0], 26 * i16::MAX as i32);
+        // the activation is clipped to QA
+        assert_eq!(nnue.evaluate(&state), (QA * EVAL_SCALE / (QA * QB)) as i16);
+    }
+}
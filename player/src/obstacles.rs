@@ -0,0 +1,85 @@
+use game_sdk::bitboard::constants::VALID_FIELDS;
+use game_sdk::bitboard::get_neighbours;
+
+/// A bee on a square with at least this many blocked neighbours is half surrounded for the whole game
+pub const HALF_SURROUNDED_BLOCKED: u8 = 3;
+
+/// Data that only depends on the obstacles, computed once per game
+#[derive(Clone)]
+pub struct ObstacleInfo {
+    pub obstacles: u128,
+    /// Neighbours of every square that are on the board and not an obstacle
+    pub free_neighbours: [u128; 121],
+    /// Neighbours of every square that are obstacles or off the board. They can never be freed,
+    /// so a bee there needs fewer pieces to be surrounded
+    pub blocked_neighbours: [u8; 121],
+    /// Squares with at least `HALF_SURROUNDED_BLOCKED` blocked neighbours
+    pub half_surrounded: u128,
+    /// Free squares next to an obstacle
+    pub obstacle_adjacent: u128,
+}
+
+impl ObstacleInfo {
+    pub fn new(obstacles: u128) -> ObstacleInfo {
+        let mut free_neighbours = [0u128; 121];
+        let mut blocked_neighbours = [0u8; 121];
+        let mut half_surrounded = 0u128;
+        let mut squares = VALID_FIELDS & !obstacles;
+        while squares > 0 {
+            let square = squares.trailing_zeros() as usize;
+            squares ^= 1u128 << square;
+            free_neighbours[square] = get_neighbours(1u128 << square) & !obstacles;
+            blocked_neighbours[square] = 6 - free_neighbours[square].count_ones() as u8;
+            if blocked_neighbours[square] >= HALF_SURROUNDED_BLOCKED {
+                half_surrounded |= 1u128 << square;
+            }
+        }
+        ObstacleInfo {
+            obstacles,
+            free_neighbours,
+            blocked_neighbours,
+            half_surrounded,
+            obstacle_adjacent: get_neighbours(obstacles) & !obstacles,
+        }
+    }
+
+    /// Free neighbours of all squares in `bitboard`, excluding the squares themselves
+    pub fn free_neighbours_of(&self, mut bitboard: u128) -> u128 {
+        let mut res = 0u128;
+        let squares = bitboard;
+        while bitboard > 0 {
+            let square = bitboard.trailing_zeros() as usize;
+            bitboard ^= 1u128 << square;
+            res |= self.free_neighbours[square];
+        }
+        res & !squares
+    }
+}
+
+impl Default for ObstacleInfo {
+    fn default() -> Self {
+        ObstacleInfo::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ObstacleInfo;
+
+    #[test]
+    fn edges_and_obstacles_block_squares() {
+        // 60 is the center, 61 and 71 are two of its neighbours
+        let info = ObstacleInfo::new((1u128 << 61) | (1u128 << 71));
+        assert_eq!(info.blocked_neighbours[60], 2);
+        assert_eq!(info.free_neighbours[60].count_ones(), 4);
+        // corner of the board, one neighbour is an obstacle
+        let info = ObstacleInfo::new(1u128 << 1);
+        assert_eq!(info.blocked_neighbours[0], 4);
+        assert!(info.half_surrounded & 1 > 0);
+        assert!(info.obstacle_adjacent & 1 > 0);
+        assert_eq!(
+            info.free_neighbours_of(1 | (1u128 << 11)),
+            (1u128 << 12) | (1u128 << 22) | (1u128 << 23)
+        );
+    }
+}
@@ -0,0 +1,15 @@
+/// Players whose settings can be changed at runtime, e.g. by the referee or command line arguments
+pub trait Configurable {
+    /// Sets the option `name` to `value`. Unknown options and invalid values are reported as error
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), String>;
+
+    /// Forgets everything learned about the previous game
+    fn new_game(&mut self);
+}
+
+/// Parses the value of a numeric option
+pub fn parse_value<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid value {} for option {}", value, name))
+}
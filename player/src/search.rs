@@ -1,129 +1,267 @@
+use crate::book::Book;
 use crate::cache::{Cache, CacheEntry, EvalCache, EvalCacheEntry, HASH_SIZE};
-use crate::evaluation::evaluate;
+use crate::evaluation::HandCrafted;
+use crate::evaluator::{evaluator_from_option, parameter_evaluator, Evaluator};
+use crate::learning::{LearningEntry, LearningStore, DEFAULT_LEARNING_SIZE, MIN_LEARNING_DEPTH};
 use crate::moveordering::{MoveOrderer, STAGES};
-use crate::timecontrol::Timecontrol;
+use crate::obstacles::ObstacleInfo;
+use crate::options::{parse_value, Configurable};
+use crate::searchhandle::Interruptible;
+use crate::searchinfo::{InfoPrinter, ScoreType, SearchInfo, SearchListener};
+use crate::searchlimits::SearchLimits;
+use crate::timecontrol::{TimeManager, Timecontrol, DEFAULT_GAME_TIME};
 use game_sdk::actionlist::ActionListStack;
+use game_sdk::bitboard::get_neighbours;
 use game_sdk::gamerules::{calculate_legal_moves, get_result, is_game_finished};
 use game_sdk::{
     Action, ActionList, ClientListener, Color, GameState, PieceType, MATED_IN_MAX, MATE_IN_MAX,
 };
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Instant;
 
 pub const STANDARD_SCORE: i16 = std::i16::MIN + 1;
 pub const MAX_SEARCH_DEPTH: usize = 60;
 pub const DEBUG_PRUNING: bool = false;
+/// Minimum remaining depth for an internal iterative deepening search at PV nodes without a TT move
+pub const IID_MIN_DEPTH: usize = 4;
+pub const IID_REDUCTION: usize = 2;
+/// Minimum remaining depth for checking if the TT move is singular
+pub const SINGULAR_MIN_DEPTH: usize = 6;
+/// The TT entry may be this many plies shallower than the current node to be used for singularity checks
+pub const SINGULAR_TT_DEPTH_MARGIN: usize = 3;
+/// Per ply of remaining depth, all other moves have to be this much worse than the TT move to extend it
+pub const SINGULAR_MARGIN_PER_DEPTH: i16 = 4;
+/// Minimum depth at which the best root move is checked for dominating all others
+pub const DOMINANCE_MIN_DEPTH: usize = 6;
+/// All other root moves have to be this much worse than the best one for it to dominate
+pub const DOMINANCE_MARGIN: i16 = 60;
+
+// Indices into `cutoff_stats`. 0 and 1 count null move cutoffs if `DEBUG_PRUNING` is set
+pub const STATS_IID_SEARCHES: usize = 2;
+pub const STATS_IID_MOVE_BEST: usize = 3;
+pub const STATS_SINGULAR_SEARCHES: usize = 4;
+pub const STATS_SINGULAR_EXTENSIONS: usize = 5;
+
+/// Search constants that can be changed at runtime, e.g. to tune them
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchParameters {
+    /// Depth reduction of the null move search
+    pub null_move_reduction: usize,
+    /// Null moves are only tried with more remaining depth than this
+    pub null_move_min_depth: usize,
+    /// The history scores are divided by this before every search
+    pub history_decay: usize,
+    /// Maximum amount of plies a single line is extended because a bee is about to be surrounded,
+    /// 0 turns it off
+    pub bee_threat_max_extensions: usize,
+    /// Maximum depth of the quiescence search that only resolves bee threats, 0 turns it off
+    pub bee_qsearch_max_depth: usize,
+}
+
+impl Default for SearchParameters {
+    fn default() -> Self {
+        SearchParameters {
+            null_move_reduction: 3,
+            null_move_min_depth: 3,
+            history_decay: 8,
+            // both bee threat features lost elo against the plain search in referee matches
+            // (30000 nodes and 300ms per move), so they are off until the evaluation catches up
+            bee_threat_max_extensions: 0,
+            bee_qsearch_max_depth: 0,
+        }
+    }
+}
 
 pub struct Searcher {
     pub nodes_searched: u64,
+    pub seldepth: usize,
     pub als: ActionListStack,
     pub start_time: Option<Instant>,
     pub principal_variation_table: ActionList<Action>,
     pub principal_variation_hashtable: Vec<u64>,
     pub pv_table: ActionListStack,
     pub stop_flag: bool,
+    pub root_depth: usize,
+    /// Set once the first root move of the current iteration has been searched completely
+    pub root_first_move_done: bool,
+    /// Best score of the fully searched root moves of the current iteration
+    pub root_score: i16,
+    pub stop_signal: Arc<AtomicBool>,
+    pub listeners: Vec<Box<dyn SearchListener>>,
     pub cache: Cache,
     pub eval_cache: EvalCache,
     pub root_plies_played: u8,
+    /// Time that is left, it is reset to `game_tc` at the start of every game
     pub tc: Timecontrol,
+    pub game_tc: Timecontrol,
+    pub time_manager: TimeManager,
+    pub limits: SearchLimits,
+    pub deterministic: bool,
     pub killer_moves: [[Option<Action>; 2]; MAX_SEARCH_DEPTH],
+    /// Move that is skipped at a depth while checking if it is singular
+    pub excluded_moves: [Option<Action>; MAX_SEARCH_DEPTH],
     pub hh_score: [[[usize; 122]; 122]; 2],
     pub bf_score: [[[usize; 122]; 122]; 2],
     pub cutoff_stats: Vec<u64>,
+    /// Results of deep searches that are kept across games, off unless a file is set
+    pub learning: Option<LearningStore>,
+    /// Set once the learning store has been copied into the cache
+    pub learning_seeded: bool,
+    /// Probed before every search, off unless a file is set
+    pub book: Option<Book>,
+    /// Prepared at the start of every game, or as soon as a search sees other obstacles
+    pub obstacle_info: ObstacleInfo,
+    pub evaluator: Box<dyn Evaluator>,
+    pub params: SearchParameters,
 }
 
 impl Searcher {
     pub fn new() -> Self {
         Searcher {
             nodes_searched: 0,
+            seldepth: 0,
             als: ActionListStack::with_size(MAX_SEARCH_DEPTH),
             start_time: None,
             principal_variation_table: ActionList::default(),
             principal_variation_hashtable: Vec::with_capacity(MAX_SEARCH_DEPTH),
             pv_table: ActionListStack::with_size(MAX_SEARCH_DEPTH),
             stop_flag: false,
+            root_depth: 0,
+            root_first_move_done: false,
+            root_score: STANDARD_SCORE,
+            stop_signal: Arc::new(AtomicBool::new(false)),
+            listeners: vec![Box::new(InfoPrinter)],
             cache: Cache::with_size(HASH_SIZE),
             eval_cache: EvalCache::with_size(HASH_SIZE),
             root_plies_played: 0,
-            tc: Timecontrol::MoveTime(1800),
+            tc: Timecontrol::GameTime(DEFAULT_GAME_TIME),
+            game_tc: Timecontrol::GameTime(DEFAULT_GAME_TIME),
+            time_manager: TimeManager::new(Timecontrol::GameTime(DEFAULT_GAME_TIME), 0),
+            limits: SearchLimits::none(),
+            deterministic: false,
             killer_moves: [[None; 2]; MAX_SEARCH_DEPTH],
+            excluded_moves: [None; MAX_SEARCH_DEPTH],
             hh_score: [[[0usize; 122]; 122]; 2],
             bf_score: [[[1usize; 122]; 122]; 2],
             cutoff_stats: vec![0; 60],
+            learning: None,
+            learning_seeded: false,
+            book: None,
+            obstacle_info: ObstacleInfo::default(),
+            evaluator: Box::new(HandCrafted::default()),
+            params: SearchParameters::default(),
         }
     }
     pub fn with_tc(tc: Timecontrol) -> Self {
         let mut res = Searcher::new();
-        res.tc = tc;
+        res.set_tc(tc);
         res
     }
 
-    pub fn format_pv(pv: &ActionList<Action>) -> String {
-        let mut ret = "".to_owned();
-
-        for i in 0..pv.size {
-            match pv[i] {
-                Action::SkipMove => ret.push_str("Skip"),
-                Action::SetMove(piece_type, to) => {
-                    let mut part = "Set(".to_owned();
-                    part.push_str(&Searcher::format_piecetype_pv(piece_type));
-                    part.push_str(&format!(", {})", to));
-                    ret.push_str(&part);
-                }
-                Action::DragMove(piece_type, from, to) => {
-                    let mut part = "Drag(".to_owned();
-                    part.push_str(&Searcher::format_piecetype_pv(piece_type));
-                    part.push_str(&format!(", {}, {})", from, to));
-                    ret.push_str(&part);
-                }
-            }
-            if i != pv.size - 1 {
-                ret.push_str(" ");
-            }
-        }
+    /// Sets the timecontrol of this and of every following game
+    pub fn set_tc(&mut self, tc: Timecontrol) {
+        self.tc = tc;
+        self.game_tc = tc;
+    }
 
-        ret
+    /// Searcher that never looks at the clock and forgets everything between searches,
+    /// so the same position always results in the same move. Needs a node or depth limit
+    pub fn deterministic(limits: SearchLimits) -> Self {
+        let mut res = Searcher::with_tc(Timecontrol::Infinite);
+        res.limits = limits;
+        res.deterministic = true;
+        res
     }
 
-    fn format_piecetype_pv(piece_type: PieceType) -> String {
-        match piece_type {
-            PieceType::BEE => "Q",
-            PieceType::BEETLE => "B",
-            PieceType::ANT => "A",
-            PieceType::GRASSHOPPER => "G",
-            PieceType::SPIDER => "S",
-        }
-        .to_owned()
+    /// Resets all state that is carried over from one search to the next
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.eval_cache.clear();
+        self.principal_variation_table.clear();
+        self.principal_variation_hashtable.clear();
+        self.root_plies_played = 0;
+        self.killer_moves = [[None; 2]; MAX_SEARCH_DEPTH];
+        self.hh_score = [[[0usize; 122]; 122]; 2];
+        self.bf_score = [[[1usize; 122]; 122]; 2];
+        self.cutoff_stats = vec![0; 60];
+        self.learning_seeded = false;
     }
 
     pub fn search_move(&mut self, game_state: &GameState) -> Action {
-        println!("Searching state w/ fen:{}", game_state.to_fen());
+        for listener in self.listeners.iter_mut() {
+            listener.on_search_start(game_state);
+        }
         let mut al = ActionList::default();
         calculate_legal_moves(&game_state, &mut al);
         if al.size == 0 {
             panic!("There are no legal moves in this position! What should I return?");
         }
+        if self.deterministic {
+            assert!(
+                self.limits.nodes.is_some() || self.limits.depth.is_some(),
+                "A deterministic search needs a node or depth limit"
+            );
+            self.clear();
+        }
+        if let (Some(learning), false) = (&self.learning, self.learning_seeded) {
+            learning.seed_cache(&mut self.cache, game_state);
+            self.learning_seeded = true;
+        }
+        if self.obstacle_info.obstacles != game_state.obstacles {
+            self.obstacle_info = ObstacleInfo::new(game_state.obstacles);
+        }
+        self.evaluator.prepare(game_state);
         let mut game_state = game_state.clone();
         self.nodes_searched = 0;
+        self.seldepth = 0;
         self.start_time = Some(Instant::now());
         self.principal_variation_table.clear();
         self.principal_variation_hashtable.clear();
         self.stop_flag = false;
+        self.time_manager = TimeManager::new(self.tc, game_state.ply);
+        if al.size == 1 {
+            self.time_manager.set_dominant();
+        }
         self.root_plies_played = game_state.ply;
         self.killer_moves = [[None; 2]; MAX_SEARCH_DEPTH];
+        self.excluded_moves = [None; MAX_SEARCH_DEPTH];
         self.cutoff_stats = vec![0; 60];
         for i in 0..2 {
             for j in 0..122 {
                 for k in 0..122 {
-                    self.hh_score[i][j][k] /= 8;
-                    self.bf_score[i][j][k] = (self.bf_score[i][j][k] / 8).max(1);
+                    self.hh_score[i][j][k] /= self.params.history_decay;
+                    self.bf_score[i][j][k] =
+                        (self.bf_score[i][j][k] / self.params.history_decay).max(1);
                 }
             }
         }
 
-        let mut score = STANDARD_SCORE;
+        let mut last_info = SearchInfo::default();
         let mut terminal_found = false;
         let mut best_terminal = None;
-        for depth in 1..=MAX_SEARCH_DEPTH {
+        let mut last_score = 0;
+        let mut dominance_checked = false;
+        let mut last_iteration_nodes = 0;
+        let mut last_branching_factor: Option<f64> = None;
+        let mut predicted_time = 0;
+        let mut completed = None;
+        for depth in 1..=self.limits.max_depth() {
+            let iteration_start = self.start_time.unwrap().elapsed().as_millis() as u64;
+            if depth > 1
+                && !self
+                    .time_manager
+                    .start_iteration(iteration_start, predicted_time)
+            {
+                break;
+            }
+            let nodes_before = self.nodes_searched;
+            self.root_depth = depth;
+            self.root_first_move_done = false;
+            self.root_score = STANDARD_SCORE;
             let new_score = principal_variation_search(
                 self,
                 &mut game_state,
@@ -133,10 +271,52 @@ impl Searcher {
                 -STANDARD_SCORE,
             );
             if self.stop_flag {
+                if self.root_first_move_done {
+                    // the best move of the aborted iteration is at least as good as the previous one,
+                    // as the previous one is always searched first
+                    self.principal_variation_table =
+                        Searcher::validate_pv(&game_state, &self.pv_table[0]);
+                    let suppressed = matches!(best_terminal, Some(best_terminal) if self.root_score <= best_terminal);
+                    if !suppressed {
+                        let info = self.search_info(&game_state, depth, self.root_score);
+                        for listener in self.listeners.iter_mut() {
+                            listener.on_search_info(&info);
+                        }
+                        last_info = info;
+                    }
+                }
                 break;
             }
-            score = new_score;
-            self.principal_variation_table = self.pv_table[0].clone();
+            // predict the duration of the next iteration from the effective branching factor
+            let iteration_nodes = self.nodes_searched - nodes_before;
+            let iteration_time =
+                self.start_time.unwrap().elapsed().as_millis() as u64 - iteration_start;
+            if last_iteration_nodes > 0 {
+                let branching_factor = iteration_nodes as f64 / last_iteration_nodes as f64;
+                // odd and even depths differ a lot, so two iterations are averaged
+                let smoothed = match last_branching_factor {
+                    Some(last) => (branching_factor * last).sqrt(),
+                    None => branching_factor,
+                };
+                last_branching_factor = Some(branching_factor);
+                predicted_time = (iteration_time as f64 * smoothed) as u64;
+            }
+            last_iteration_nodes = iteration_nodes;
+            let score = new_score;
+            let last_best_action = if self.principal_variation_table.size > 0 {
+                Some(self.principal_variation_table[0])
+            } else {
+                None
+            };
+            self.principal_variation_table = Searcher::validate_pv(&game_state, &self.pv_table[0]);
+            if depth > 1 {
+                self.time_manager.update(
+                    last_best_action != Some(self.principal_variation_table[0]),
+                    score.saturating_sub(last_score),
+                );
+            }
+            last_score = score;
+            completed = Some((depth, score, self.principal_variation_table.clone()));
             let mut toy_state = game_state.clone();
             self.principal_variation_hashtable.clear();
             for i in 0..self.principal_variation_table.size {
@@ -144,8 +324,32 @@ impl Searcher {
                 toy_state.make_action(self.principal_variation_table[i]);
             }
 
-            let nps =
-                self.nodes_searched as f64 / (self.start_time.unwrap().elapsed().as_secs_f64());
+            if !dominance_checked
+                && depth >= DOMINANCE_MIN_DEPTH
+                && score.abs() < MATE_IN_MAX
+                && matches!(self.tc, Timecontrol::GameTime(_))
+            {
+                // search all other root moves with reduced depth against a lowered bound
+                dominance_checked = true;
+                let dominance_beta = score - DOMINANCE_MARGIN;
+                self.excluded_moves[0] = Some(self.principal_variation_table[0]);
+                let other_score = principal_variation_search(
+                    self,
+                    &mut game_state,
+                    0,
+                    depth / 2,
+                    dominance_beta - 1,
+                    dominance_beta,
+                );
+                self.excluded_moves[0] = None;
+                if self.stop_flag {
+                    break;
+                }
+                if other_score < dominance_beta {
+                    self.time_manager.set_dominant();
+                }
+            }
+
             if terminal_found {
                 if let Some(best_terminal) = best_terminal {
                     if score <= best_terminal {
@@ -154,23 +358,17 @@ impl Searcher {
                     }
                 }
             }
-            println!(
-                "info depth {} score {} bestmove {:?} nodes {} nps {:.2} time {} hashfull {} pv {}",
-                depth,
-                score,
-                self.principal_variation_table[0],
-                self.nodes_searched,
-                nps,
-                self.start_time.unwrap().elapsed().as_millis(),
-                self.cache.fill_status(),
-                Searcher::format_pv(&self.principal_variation_table)
-            );
+            let info = self.search_info(&game_state, depth, score);
+            for listener in self.listeners.iter_mut() {
+                listener.on_search_info(&info);
+            }
+            last_info = info;
             if DEBUG_PRUNING {
                 println!("{:?}", self.cutoff_stats);
-                let sum: u64 = self.cutoff_stats.iter().sum();
+                let sum: u64 = self.cutoff_stats[..2].iter().sum();
                 println!(
                     "{:?}",
-                    self.cutoff_stats
+                    self.cutoff_stats[..2]
                         .iter()
                         .map(|s| *s as f64 / (sum as f64).max(1.))
                         .collect::<Vec<f64>>()
@@ -180,21 +378,274 @@ impl Searcher {
                 terminal_found = true;
                 best_terminal = Some(score);
             }
+            if self.limits.mate_found(score) {
+                break;
+            }
         }
-        println!(
-            "Finished search with move {:?} and score {}, pv: {}",
-            self.principal_variation_table[0],
+        if self.principal_variation_table.size == 0 {
+            // stopped before the first iteration was completed
+            self.principal_variation_table.push(al[0]);
+        }
+        if last_info.pv.is_empty() {
+            last_info.pv = SearchInfo::pv_from_actionlist(&self.principal_variation_table);
+        }
+        let action = self.principal_variation_table[0];
+        for listener in self.listeners.iter_mut() {
+            listener.on_search_finished(action, &last_info);
+        }
+        self.tc = self
+            .tc
+            .spend(self.start_time.unwrap().elapsed().as_millis() as u64);
+        if let Some((depth, score, pv)) = completed {
+            self.learn(&game_state, depth, score, &pv);
+        }
+        action
+    }
+
+    /// Stores the positions along the pv of a completed iteration in the learning store.
+    /// The scores alternate along the pv, as every entry is stored from the view of the player to move
+    fn learn(&mut self, game_state: &GameState, depth: usize, score: i16, pv: &ActionList<Action>) {
+        let learning = match &mut self.learning {
+            Some(learning) => learning,
+            None => return,
+        };
+        if score.abs() >= MATE_IN_MAX {
+            // mate scores depend on the distance to the root
+            return;
+        }
+        let mut state = game_state.clone();
+        let mut score = score;
+        for i in 0..pv.size {
+            let depth_left = depth.saturating_sub(i);
+            if depth_left < MIN_LEARNING_DEPTH as usize {
+                break;
+            }
+            let entry = LearningEntry {
+                action: pv[i],
+                score,
+                depth: depth_left as u8,
+                plies: state.ply,
+            };
+            learning.insert(&state, entry);
+            state.make_action(pv[i]);
+            score = -score;
+        }
+    }
+
+    /// Writes the learning store to its file if it has changed since the last save
+    pub fn save_learning(&mut self) -> io::Result<()> {
+        match &mut self.learning {
+            Some(learning) if learning.is_modified() => learning.save(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Saves the learning store and reports a failure to the listeners, for callers that can not return it
+    fn save_learning_or_report(&mut self) {
+        if let Err(e) = self.save_learning() {
+            let message = format!(
+                "Could not save learning file {}: {}",
+                self.learning.as_ref().unwrap().path.display(),
+                e
+            );
+            for listener in self.listeners.iter_mut() {
+                listener.on_error(&message);
+            }
+        }
+    }
+
+    /// Replays `pv` from `game_state` and cuts it off before the first move that is not legal
+    pub fn validate_pv(game_state: &GameState, pv: &ActionList<Action>) -> ActionList<Action> {
+        let mut res = ActionList::default();
+        let mut state = game_state.clone();
+        let mut legal_moves = ActionList::default();
+        for i in 0..pv.size {
+            if is_game_finished(&state) {
+                break;
+            }
+            legal_moves.clear();
+            calculate_legal_moves(&state, &mut legal_moves);
+            if legal_moves.find_action(pv[i]).is_none() {
+                break;
+            }
+            state.make_action(pv[i]);
+            res.push(pv[i]);
+        }
+        res
+    }
+
+    /// Appends the moves stored in the transposition table to an already validated `pv`,
+    /// until the game is over, an entry is missing or a position would be repeated
+    pub fn extend_pv(&self, game_state: &GameState, pv: &mut ActionList<Action>) {
+        let mut state = game_state.clone();
+        let mut seen = Vec::with_capacity(MAX_SEARCH_DEPTH);
+        for i in 0..pv.size {
+            seen.push(state.hash);
+            state.make_action(pv[i]);
+        }
+        let mut legal_moves = ActionList::default();
+        while pv.size < MAX_SEARCH_DEPTH && !is_game_finished(&state) {
+            if seen.contains(&state.hash) {
+                break;
+            }
+            let action = match self.cache.lookup(state.hash) {
+                Some(ce) => ce.action,
+                None => break,
+            };
+            legal_moves.clear();
+            calculate_legal_moves(&state, &mut legal_moves);
+            if legal_moves.find_action(action).is_none() {
+                break;
+            }
+            seen.push(state.hash);
+            state.make_action(action);
+            pv.push(action);
+        }
+    }
+
+    fn search_info(&self, game_state: &GameState, depth: usize, score: i16) -> SearchInfo {
+        let elapsed = self.start_time.unwrap().elapsed();
+        let mut pv = self.principal_variation_table.clone();
+        self.extend_pv(game_state, &mut pv);
+        SearchInfo {
+            depth,
+            seldepth: self.seldepth.max(pv.size),
             score,
-            Searcher::format_pv(&self.principal_variation_table)
-        );
-        self.principal_variation_table[0]
+            score_type: Some(ScoreType::Evaluation),
+            mate: SearchInfo::mate_from_score(score),
+            nodes: self.nodes_searched,
+            nps: self.nodes_searched as f64 / elapsed.as_secs_f64(),
+            time: elapsed.as_millis() as u64,
+            hashfull: Some(self.cache.fill_status()),
+            pv: SearchInfo::pv_from_actionlist(&pv),
+        }
     }
 }
 impl ClientListener for Searcher {
+    fn on_game_start(&mut self, state: &GameState) {
+        self.obstacle_info = ObstacleInfo::new(state.obstacles);
+        self.evaluator.prepare(state);
+    }
+
     fn on_move_request(&mut self, state: &GameState) -> Action {
+        if let Some(book) = &self.book {
+            let book_move = if self.deterministic {
+                book.probe(state, &mut SmallRng::seed_from_u64(state.hash))
+            } else {
+                book.probe(state, &mut rand::thread_rng())
+            };
+            if let Some(action) = book_move {
+                for listener in self.listeners.iter_mut() {
+                    listener.on_book_move(action);
+                }
+                return action;
+            }
+        }
         self.search_move(state)
     }
 }
+impl Configurable for Searcher {
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "hash" => self
+                .cache
+                .resize(parse_value(name, value)?)
+                .map_err(|e| e.to_string()),
+            "evalhash" => self
+                .eval_cache
+                .resize(parse_value(name, value)?)
+                .map_err(|e| e.to_string()),
+            "learningfile" if value.is_empty() => {
+                self.save_learning().map_err(|e| e.to_string())?;
+                self.learning = None;
+                Ok(())
+            }
+            "learningfile" => {
+                let max_entries = self
+                    .learning
+                    .as_ref()
+                    .map_or(DEFAULT_LEARNING_SIZE, |learning| learning.max_entries);
+                let learning = LearningStore::load(value, max_entries)
+                    .map_err(|e| format!("Could not load {}: {}", value, e))?;
+                self.save_learning().map_err(|e| e.to_string())?;
+                self.learning = Some(learning);
+                self.learning_seeded = false;
+                Ok(())
+            }
+            "bookfile" if value.is_empty() => {
+                self.book = None;
+                Ok(())
+            }
+            "bookfile" => {
+                let book =
+                    Book::load(value).map_err(|e| format!("Could not load {}: {}", value, e))?;
+                self.book = Some(book);
+                Ok(())
+            }
+            "evalfile" | "evaluator" => {
+                self.evaluator = evaluator_from_option(value)?;
+                self.eval_cache.clear();
+                Ok(())
+            }
+            "evalparams" => {
+                self.evaluator = parameter_evaluator(value)?;
+                self.eval_cache.clear();
+                Ok(())
+            }
+            "nmpreduction" => {
+                self.params.null_move_reduction = parse_value(name, value)?;
+                Ok(())
+            }
+            "nmpdepth" => {
+                self.params.null_move_min_depth = parse_value(name, value)?;
+                Ok(())
+            }
+            "historydecay" => match parse_value(name, value)? {
+                0 => Err("historydecay has to be positive".to_owned()),
+                decay => {
+                    self.params.history_decay = decay;
+                    Ok(())
+                }
+            },
+            "beeextensions" => {
+                self.params.bee_threat_max_extensions = parse_value(name, value)?;
+                Ok(())
+            }
+            "beeqsearch" => {
+                self.params.bee_qsearch_max_depth = parse_value(name, value)?;
+                Ok(())
+            }
+            "learningsize" => match &mut self.learning {
+                Some(learning) => {
+                    learning.set_max_entries(parse_value(name, value)?);
+                    Ok(())
+                }
+                None => Err("learningsize needs a learningfile".to_owned()),
+            },
+            _ => Err(format!("Unknown option {}", name)),
+        }
+    }
+
+    fn new_game(&mut self) {
+        self.save_learning_or_report();
+        self.clear();
+        self.tc = self.game_tc;
+    }
+}
+impl Drop for Searcher {
+    fn drop(&mut self) {
+        self.save_learning_or_report();
+    }
+}
+impl Interruptible for Searcher {
+    fn stop_signal(&self) -> Arc<AtomicBool> {
+        self.stop_signal.clone()
+    }
+
+    fn listeners(&mut self) -> &mut Vec<Box<dyn SearchListener>> {
+        &mut self.listeners
+    }
+}
 pub fn principal_variation_search(
     searcher: &mut Searcher,
     game_state: &mut GameState,
@@ -204,6 +655,7 @@ pub fn principal_variation_search(
     mut beta: i16,
 ) -> i16 {
     searcher.nodes_searched += 1;
+    searcher.seldepth = searcher.seldepth.max(current_depth);
     //clear_pv
     searcher.pv_table[current_depth].clear();
     let root = current_depth == 0;
@@ -214,35 +666,40 @@ pub fn principal_variation_search(
         -1
     };
     let original_alpha = alpha;
+    let excluded_action = searcher.excluded_moves[current_depth];
 
+    // the first iteration is always completed, so there is a move to return
+    if searcher.principal_variation_table.size > 0
+        && searcher.limits.nodes_reached(searcher.nodes_searched)
+    {
+        searcher.stop_flag = true;
+        return STANDARD_SCORE;
+    }
     if searcher.nodes_searched % 4096 == 0 {
-        if searcher.tc.time_over(
-            searcher
-                .start_time
-                .expect("No start time set")
-                .elapsed()
-                .as_millis() as u64,
-        ) {
+        if searcher.stop_signal.load(Ordering::Relaxed)
+            || !searcher.deterministic
+                && searcher.time_manager.time_over(
+                    searcher
+                        .start_time
+                        .expect("No start time set")
+                        .elapsed()
+                        .as_millis() as u64,
+                )
+        {
             searcher.stop_flag = true;
             return STANDARD_SCORE;
         }
     }
     if searcher.nodes_searched % 10000000 == 0 {
-        println!(
-            "info nps {}",
-            searcher.nodes_searched as f64 / (searcher.start_time.unwrap().elapsed().as_secs_f64())
-        );
+        let nps =
+            searcher.nodes_searched as f64 / (searcher.start_time.unwrap().elapsed().as_secs_f64());
+        for listener in searcher.listeners.iter_mut() {
+            listener.on_nodes(searcher.nodes_searched, nps);
+        }
     }
     //Check game over
     if is_game_finished(game_state) {
-        let winner = get_result(game_state);
-        if winner.is_none() {
-            return 0;
-        } else if winner.unwrap() == Color::RED {
-            return (MATE_IN_MAX + 60 - current_depth as i16) * color;
-        } else {
-            return (MATE_IN_MAX + 60 - current_depth as i16) * -color;
-        }
+        return terminal_score(game_state, current_depth, color);
     }
     debug_assert!(current_depth < 60);
 
@@ -257,23 +714,21 @@ pub fn principal_variation_search(
         }
     }
 
-    // Quiescence search was tested and could not be made to work
+    // A full quiescence search was tested and could not be made to work,
+    // so only threats against the bees are resolved
     if depth_left <= 0 {
-        let ce = searcher.eval_cache.lookup(game_state.hash);
-        if let Some(ce) = ce {
-            return ce.score;
+        let qsearch_depth = searcher.params.bee_qsearch_max_depth;
+        if qsearch_depth > 0 && is_bee_threatened(game_state) {
+            return bee_quiescence_search(
+                searcher,
+                game_state,
+                current_depth,
+                qsearch_depth,
+                alpha,
+                beta,
+            );
         }
-        //return eval
-        let evaluation = evaluate(game_state) * color;
-        searcher.eval_cache.insert(
-            game_state.hash,
-            EvalCacheEntry {
-                upper_hash: (game_state.hash >> 32) as u32,
-                lower_hash: (game_state.hash & 0xFFFFFFFF) as u32,
-                score: evaluation,
-            },
-        );
-        return evaluation;
+        return evaluate_cached(searcher, game_state);
     }
 
     let pv_action = if searcher.principal_variation_table.size > current_depth
@@ -284,9 +739,10 @@ pub fn principal_variation_search(
         None
     };
 
-    //TT-Lookup
+    //TT-Lookup, skipped while checking for singularity as the entry belongs to the full search
     let mut tt_action: Option<Action> = None;
-    {
+    let mut tt_entry: Option<CacheEntry> = None;
+    if excluded_action.is_none() {
         let ce = searcher.cache.lookup(game_state.hash);
         if let Some(ce) = ce {
             if ce.depth >= depth_left as u8
@@ -322,32 +778,35 @@ pub fn principal_variation_search(
                 }
             }
             tt_action = Some(ce.action);
+            tt_entry = Some(ce);
         }
     }
 
     #[allow(unused_mut)]
     let mut wouldnmp = false;
     //Null move Pruning
-    if !pv_node && (!game_state.must_player_place_bee() || game_state.has_player_placed_bee() )// not necessary but should be speedup
-        && depth_left > 3
+    if !pv_node && excluded_action.is_none() && (!game_state.must_player_place_bee() || game_state.has_player_placed_bee() )// not necessary but should be speedup
+        && depth_left > searcher.params.null_move_min_depth
         && (game_state.ply + depth_left as u8) < 60
         && (game_state
         .valid_set_destinations(game_state.color_to_move)
         .count_ones()
         > 0)
-        && evaluate(&game_state) * color >= beta
+        && searcher.evaluator.evaluate(game_state) >= beta
     {
         let action = Action::SkipMove;
-        game_state.make_action(action);
+        make_action(searcher, game_state, action);
         let following_score = -principal_variation_search(
             searcher,
             game_state,
             current_depth + 1,
-            (depth_left - 3).max(1) as usize,
+            depth_left
+                .saturating_sub(searcher.params.null_move_reduction)
+                .max(1),
             -beta,
             -beta + 1,
         );
-        game_state.unmake_action(action);
+        unmake_action(searcher, game_state, action);
         if following_score >= beta {
             return following_score;
             {
@@ -359,14 +818,93 @@ pub fn principal_variation_search(
         }
     }
 
+    //Internal iterative deepening, to have a good first move at PV nodes
+    let mut iid_action = None;
+    if pv_node
+        && pv_action.is_none()
+        && tt_action.is_none()
+        && excluded_action.is_none()
+        && depth_left >= IID_MIN_DEPTH
+    {
+        searcher.cutoff_stats[STATS_IID_SEARCHES] += 1;
+        principal_variation_search(
+            searcher,
+            game_state,
+            current_depth,
+            depth_left - IID_REDUCTION,
+            alpha,
+            beta,
+        );
+        if searcher.stop_flag {
+            return STANDARD_SCORE;
+        }
+        tt_action = searcher.cache.lookup(game_state.hash).map(|ce| ce.action);
+        iid_action = tt_action;
+    }
+
+    //Singular extension: extend the TT move if all other moves fail low against a lowered bound
+    let mut singular_action = None;
+    if let Some(ce) = tt_entry {
+        if !root
+            && excluded_action.is_none()
+            && depth_left >= SINGULAR_MIN_DEPTH
+            && ce.depth as usize + SINGULAR_TT_DEPTH_MARGIN >= depth_left
+            && (ce.beta || !ce.alpha)
+            && ce.score.abs() < MATE_IN_MAX
+            && current_depth < 2 * searcher.root_depth
+        {
+            let singular_beta = ce.score - SINGULAR_MARGIN_PER_DEPTH * depth_left as i16;
+            searcher.cutoff_stats[STATS_SINGULAR_SEARCHES] += 1;
+            searcher.excluded_moves[current_depth] = Some(ce.action);
+            let score = principal_variation_search(
+                searcher,
+                game_state,
+                current_depth,
+                (depth_left - 1) / 2,
+                singular_beta - 1,
+                singular_beta,
+            );
+            searcher.excluded_moves[current_depth] = None;
+            if searcher.stop_flag {
+                return STANDARD_SCORE;
+            }
+            if score < singular_beta {
+                searcher.cutoff_stats[STATS_SINGULAR_EXTENSIONS] += 1;
+                singular_action = Some(ce.action);
+            }
+        }
+    }
+
     let mut current_max_score = STANDARD_SCORE;
+    let max_extensions = searcher.params.bee_threat_max_extensions;
+    let threatened = max_extensions > 0 && is_bee_threatened(game_state);
 
     let mut move_orderer = MoveOrderer::with_stages(&STAGES);
     let mut i = 0;
     while let Some(action) =
         move_orderer.next(game_state, searcher, current_depth, pv_action, tt_action)
     {
-        game_state.make_action(action);
+        if Some(action) == excluded_action {
+            continue;
+        }
+        make_action(searcher, game_state, action);
+        //Extend singular TT moves and moves after which a bee can be surrounded with the next move
+        let extension = if Some(action) == singular_action
+            || max_extensions > 0
+                && current_depth + depth_left < searcher.root_depth + max_extensions
+                && !threatened
+                && is_bee_threatened(game_state)
+        {
+            1
+        } else {
+            0
+        };
+        let child_depth = depth_left - 1 + extension;
+        if child_depth == 0 {
+            searcher.eval_cache.prefetch(game_state.hash);
+        } else {
+            searcher.cache.prefetch(game_state.hash);
+        }
         //TODO: Forward pruning & late-move-reductions
         let following_score = if depth_left <= 2 || !pv_node || i == 0 {
             //Full window
@@ -374,7 +912,7 @@ pub fn principal_variation_search(
                 searcher,
                 game_state,
                 current_depth + 1,
-                depth_left - 1,
+                child_depth,
                 -beta,
                 -alpha,
             )
@@ -384,7 +922,7 @@ pub fn principal_variation_search(
                 searcher,
                 game_state,
                 current_depth + 1,
-                depth_left - 1,
+                child_depth,
                 -alpha - 1,
                 -alpha,
             );
@@ -393,14 +931,18 @@ pub fn principal_variation_search(
                     searcher,
                     game_state,
                     current_depth + 1,
-                    depth_left - 1,
+                    child_depth,
                     -beta,
                     -alpha,
                 );
             }
             following_score
         };
-        game_state.unmake_action(action);
+        unmake_action(searcher, game_state, action);
+        if root && excluded_action.is_none() && !searcher.stop_flag {
+            searcher.root_first_move_done = true;
+            searcher.root_score = searcher.root_score.max(following_score);
+        }
         if following_score > current_max_score && !searcher.stop_flag {
             current_max_score = following_score;
             searcher.pv_table[current_depth].clear();
@@ -442,6 +984,20 @@ pub fn principal_variation_search(
     } else if wouldnmp && alpha < beta {
         searcher.cutoff_stats[1] += 1;
     }
+    if excluded_action.is_some() {
+        // the excluded move was the only legal move, so it is singular
+        if current_max_score == STANDARD_SCORE {
+            return alpha;
+        }
+        return current_max_score;
+    }
+    if !searcher.stop_flag
+        && iid_action.is_some()
+        && searcher.pv_table[current_depth].size > 0
+        && iid_action == Some(searcher.pv_table[current_depth][0])
+    {
+        searcher.cutoff_stats[STATS_IID_MOVE_BEST] += 1;
+    }
     if !searcher.stop_flag && i == 0 && current_max_score == STANDARD_SCORE {
         panic!("No legal move found and tried in a position! This should never occur!");
     }
@@ -477,3 +1033,374 @@ pub fn principal_variation_search(
     }
     current_max_score
 }
+
+fn terminal_score(game_state: &GameState, current_depth: usize, color: i16) -> i16 {
+    match get_result(game_state) {
+        None => 0,
+        Some(Color::RED) => (MATE_IN_MAX + 60 - current_depth as i16) * color,
+        Some(Color::BLUE) => (MATE_IN_MAX + 60 - current_depth as i16) * -color,
+    }
+}
+
+/// Makes `action` on the state and tells the evaluator about it
+fn make_action(searcher: &mut Searcher, game_state: &mut GameState, action: Action) {
+    searcher.evaluator.make_action(game_state, action);
+    game_state.make_action(action);
+}
+
+fn unmake_action(searcher: &mut Searcher, game_state: &mut GameState, action: Action) {
+    searcher.evaluator.unmake_action();
+    game_state.unmake_action(action);
+}
+
+fn evaluate_cached(searcher: &mut Searcher, game_state: &GameState) -> i16 {
+    let ce = searcher.eval_cache.lookup(game_state.hash);
+    if let Some(ce) = ce {
+        return ce.score;
+    }
+    let evaluation = searcher.evaluator.evaluate(game_state);
+    searcher.eval_cache.insert(
+        game_state.hash,
+        EvalCacheEntry {
+            upper_hash: (game_state.hash >> 32) as u32,
+            lower_hash: (game_state.hash & 0xFFFFFFFF) as u32,
+            score: evaluation,
+        },
+    );
+    evaluation
+}
+
+/// Free neighbours of the bee of `color`, 0 if the bee has not been placed yet
+pub fn free_bee_neighbours(game_state: &GameState, color: Color) -> u128 {
+    let bee = game_state.pieces[PieceType::BEE as usize][color as usize];
+    if bee == 0 {
+        return 0;
+    }
+    get_neighbours(bee) & !game_state.occupied() & !game_state.obstacles
+}
+
+/// True if either bee has exactly one free neighbour left
+pub fn is_bee_threatened(game_state: &GameState) -> bool {
+    free_bee_neighbours(game_state, Color::RED).count_ones() == 1
+        || free_bee_neighbours(game_state, Color::BLUE).count_ones() == 1
+}
+
+/// Quiescence search that only tries moves which fill the last free neighbour of the opponent's bee.
+/// If the own bee is already surrounded, every move is tried instead, as standing pat would lose the game
+fn bee_quiescence_search(
+    searcher: &mut Searcher,
+    game_state: &mut GameState,
+    current_depth: usize,
+    depth_left: usize,
+    mut alpha: i16,
+    beta: i16,
+) -> i16 {
+    searcher.nodes_searched += 1;
+    searcher.seldepth = searcher.seldepth.max(current_depth);
+    let color = if game_state.color_to_move == Color::RED {
+        1
+    } else {
+        -1
+    };
+    if is_game_finished(game_state) {
+        return terminal_score(game_state, current_depth, color);
+    }
+    let own_bee = game_state.pieces[PieceType::BEE as usize][game_state.color_to_move as usize];
+    let surrounded = own_bee != 0 && free_bee_neighbours(game_state, game_state.color_to_move) == 0;
+    let mut best_score = STANDARD_SCORE;
+    if !surrounded {
+        best_score = evaluate_cached(searcher, game_state);
+        if depth_left == 0 || best_score >= beta {
+            return best_score;
+        }
+        alpha = alpha.max(best_score);
+    }
+    let opponent = game_state.color_to_move.swap();
+    let target = free_bee_neighbours(game_state, opponent);
+    if !surrounded && target.count_ones() != 1 {
+        return best_score;
+    }
+    let opponent_bee_neighbours =
+        get_neighbours(game_state.pieces[PieceType::BEE as usize][opponent as usize]);
+
+    calculate_legal_moves(game_state, &mut searcher.als[current_depth]);
+    for i in 0..searcher.als[current_depth].size {
+        let action = searcher.als[current_depth][i];
+        if !surrounded {
+            let fills_target = match action {
+                Action::SkipMove => false,
+                Action::SetMove(_, to) => (1u128 << to) & target != 0,
+                // moving away from the bee frees another neighbour
+                Action::DragMove(_, from, to) => {
+                    (1u128 << to) & target != 0 && (1u128 << from) & opponent_bee_neighbours == 0
+                }
+            };
+            if !fills_target {
+                continue;
+            }
+        }
+        make_action(searcher, game_state, action);
+        let following_score = -bee_quiescence_search(
+            searcher,
+            game_state,
+            current_depth + 1,
+            depth_left.saturating_sub(1),
+            -beta,
+            -alpha,
+        );
+        unmake_action(searcher, game_state, action);
+        best_score = best_score.max(following_score);
+        alpha = alpha.max(following_score);
+        if alpha >= beta {
+            break;
+        }
+    }
+    best_score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SearchParameters, Searcher};
+    use crate::book::{BookBuilder, MIN_BOOK_GAMES};
+    use crate::evaluation::{EvaluationParameters, HandCrafted, DEFAULT_ARRAY};
+    use crate::evaluator::Evaluator;
+    use crate::learning::{LearningStore, MIN_LEARNING_DEPTH};
+    use crate::options::Configurable;
+    use crate::searchinfo::{SearchInfo, SearchListener};
+    use crate::searchlimits::SearchLimits;
+    use crate::timecontrol::Timecontrol;
+    use game_sdk::gamerules::{calculate_legal_moves, is_game_finished};
+    use game_sdk::{Action, ActionList, ClientListener, Color, GameState};
+    use std::sync::{Arc, Mutex};
+
+    const FEN: &str = "20 RED 19807040637789456435240771584 576460752303423488 0 0 0 140737488355328 0 0 0 34359738368 4835703278458585418301440 140737488355328 2417851639229258349412352 8388608 2361183241434822606848 144115188109410304 0 288230376151711744 576460752303423488";
+
+    #[test]
+    fn deterministic_search_is_reproducible() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut searcher = Searcher::deterministic(SearchLimits::none().with_nodes(20000));
+        searcher.listeners.clear();
+        let first = searcher.search_move(&state);
+        let first_nodes = searcher.nodes_searched;
+        // searching another position in between must not influence the result
+        let mut other = state.clone();
+        other.make_action(first);
+        searcher.search_move(&other);
+        assert_eq!(searcher.search_move(&state), first);
+        assert_eq!(searcher.nodes_searched, first_nodes);
+    }
+
+    #[test]
+    fn pv_is_cut_at_illegal_move() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut legal_moves = ActionList::default();
+        calculate_legal_moves(&state, &mut legal_moves);
+        let mut pv = ActionList::default();
+        pv.push(legal_moves[0]);
+        // the same player can never move twice in a row
+        pv.push(legal_moves[0]);
+        pv.push(Action::SkipMove);
+        let validated = Searcher::validate_pv(&state, &pv);
+        assert_eq!(validated.size, 1);
+        assert_eq!(validated[0], legal_moves[0]);
+    }
+
+    #[test]
+    fn search_parameters_are_options() {
+        let mut searcher = Searcher::new();
+        searcher.listeners.clear();
+        searcher.set_option("nmpreduction", "2").unwrap();
+        searcher.set_option("nmpdepth", "4").unwrap();
+        searcher.set_option("historydecay", "4").unwrap();
+        searcher.set_option("beeextensions", "2").unwrap();
+        searcher.set_option("beeqsearch", "3").unwrap();
+        assert!(searcher.set_option("historydecay", "0").is_err());
+        assert_eq!(
+            searcher.params,
+            SearchParameters {
+                null_move_reduction: 2,
+                null_move_min_depth: 4,
+                history_decay: 4,
+                bee_threat_max_extensions: 2,
+                bee_qsearch_max_depth: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn search_parameters_shape_the_tree() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut searcher = Searcher::deterministic(SearchLimits::none().with_depth(6));
+        searcher.listeners.clear();
+        searcher.search_move(&state);
+        let default = searcher.nodes_searched;
+        // every parameter changes the tree of a search with a fixed depth
+        for (option, value) in [("nmpreduction", "1"), ("nmpdepth", "1")].iter() {
+            searcher.params = SearchParameters::default();
+            searcher.set_option(option, value).unwrap();
+            searcher.search_move(&state);
+            assert_ne!(searcher.nodes_searched, default, "{} {}", option, value);
+        }
+        // the history is only carried over by searches that do not clear the tables.
+        // No move goes from square 0 to itself, so the search does not touch the entry
+        searcher.deterministic = false;
+        searcher.limits = SearchLimits::none().with_depth(1);
+        searcher.params = SearchParameters::default();
+        searcher.set_option("historydecay", "4").unwrap();
+        searcher.hh_score[0][0][0] = 800;
+        searcher.search_move(&state);
+        assert_eq!(searcher.hh_score[0][0][0], 200);
+    }
+
+    #[test]
+    fn bee_threat_search_is_configurable() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut searcher = Searcher::deterministic(SearchLimits::none().with_depth(3));
+        searcher.listeners.clear();
+        let mut nodes = |option: &str| {
+            searcher.params = SearchParameters::default();
+            if !option.is_empty() {
+                searcher.set_option(option, "4").unwrap();
+            }
+            searcher.search_move(&state);
+            searcher.nodes_searched
+        };
+        let plain = nodes("");
+        // both features only search more nodes, and only in lines where a bee is threatened
+        assert!(nodes("beeqsearch") > plain);
+        assert!(nodes("beeextensions") > plain);
+    }
+
+    #[test]
+    fn new_game_restores_game_time() {
+        let budget = Timecontrol::GameTime(60000);
+        let mut searcher = Searcher::with_tc(budget);
+        searcher.listeners.clear();
+        searcher.limits = SearchLimits::none().with_nodes(2000);
+        for _ in 0..2 {
+            searcher.new_game();
+            assert_eq!(searcher.tc, budget);
+            let mut state = GameState::from_fen(FEN.to_owned());
+            // every search takes at least a few milliseconds, so the budget shrinks within a game
+            while searcher.tc == budget && !is_game_finished(&state) {
+                let action = searcher.search_move(&state);
+                state.make_action(action);
+            }
+            assert_ne!(searcher.tc, budget);
+        }
+    }
+
+    /// Records the book moves and the amount of search infos it receives
+    struct BookRecorder(Arc<Mutex<(Vec<Action>, usize)>>);
+    impl SearchListener for BookRecorder {
+        fn on_search_info(&mut self, _info: &SearchInfo) {
+            self.0.lock().unwrap().1 += 1;
+        }
+
+        fn on_book_move(&mut self, action: Action) {
+            self.0.lock().unwrap().0.push(action);
+        }
+    }
+
+    #[test]
+    fn book_moves_are_reported_to_listeners() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut legal_moves = ActionList::default();
+        calculate_legal_moves(&state, &mut legal_moves);
+        let book_move = legal_moves[legal_moves.size - 1];
+        let mut builder = BookBuilder::new(60);
+        for _ in 0..MIN_BOOK_GAMES {
+            builder.add_game(&state, &[book_move], Some(Color::RED));
+        }
+        let mut searcher = Searcher::deterministic(SearchLimits::none().with_depth(1));
+        let recorded = Arc::new(Mutex::new((Vec::new(), 0)));
+        searcher.listeners = vec![Box::new(BookRecorder(recorded.clone()))];
+        searcher.book = Some(builder.book);
+        assert_eq!(searcher.on_move_request(&state), book_move);
+        assert_eq!(*recorded.lock().unwrap(), (vec![book_move], 0));
+        // positions outside of the book are searched
+        let mut other = state.clone();
+        other.make_action(book_move);
+        searcher.on_move_request(&other);
+        let recorded = recorded.lock().unwrap();
+        assert_eq!(recorded.0, vec![book_move]);
+        assert!(recorded.1 > 0);
+    }
+
+    #[test]
+    fn learning_alternates_scores_and_saves_between_games() {
+        let path = std::env::temp_dir().join(format!("search-learning-{}.bin", std::process::id()));
+        let state = GameState::from_fen(FEN.to_owned());
+        let depth = MIN_LEARNING_DEPTH as usize + 1;
+        let mut searcher = Searcher::deterministic(SearchLimits::none().with_depth(depth));
+        let last_info = Arc::new(Mutex::new(SearchInfo::default()));
+        let recorder = last_info.clone();
+        searcher.listeners = vec![Box::new(move |info: &SearchInfo| {
+            *recorder.lock().unwrap() = info.clone();
+        })];
+        searcher.learning = Some(LearningStore::new(&path, 100));
+        let action = searcher.search_move(&state);
+        // moves are not saved one by one
+        assert!(!path.exists());
+
+        let info = last_info.lock().unwrap().clone();
+        let learning = searcher.learning.as_ref().unwrap();
+        let root = learning.get(&state).unwrap();
+        assert_eq!((root.action, root.score), (action, info.score));
+        // the opponent to move reads the score from its own view
+        let mut child = state.clone();
+        child.make_action(action);
+        let reply = learning.get(&child).unwrap();
+        assert_eq!((reply.action, reply.score), (info.pv[1], -info.score));
+
+        searcher.new_game();
+        let loaded = LearningStore::load(&path, 100).unwrap();
+        assert_eq!(loaded.get(&child), Some(reply));
+        assert!(!searcher.learning.as_ref().unwrap().is_modified());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn search_stops_at_its_limits() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let last_depth = Arc::new(Mutex::new(0));
+        let recorder = last_depth.clone();
+        let mut searcher = Searcher::deterministic(SearchLimits::none().with_depth(3));
+        searcher.listeners = vec![Box::new(move |info: &SearchInfo| {
+            *recorder.lock().unwrap() = info.depth;
+        })];
+        searcher.search_move(&state);
+        assert_eq!(*last_depth.lock().unwrap(), 3);
+        let depth_nodes = searcher.nodes_searched;
+
+        searcher.limits = SearchLimits::none().with_nodes(depth_nodes / 2);
+        searcher.search_move(&state);
+        assert!(searcher.nodes_searched >= depth_nodes / 2);
+        assert!(searcher.nodes_searched <= depth_nodes / 2 + 1);
+        assert!(*last_depth.lock().unwrap() <= 3);
+    }
+
+    #[test]
+    fn evalparams_option_sets_the_evaluation_parameters() {
+        let path = std::env::temp_dir().join(format!("search-params-{}.txt", std::process::id()));
+        let mut params = DEFAULT_ARRAY;
+        params[0] *= 3.;
+        let params = EvaluationParameters::from_array(params);
+        params.save(&path).unwrap();
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut searcher = Searcher::new();
+        let path = path.to_str().unwrap();
+        searcher.set_option("evalparams", path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(searcher.set_option("evalparams", path).is_err());
+
+        let mut expected = HandCrafted::with_parameters(params);
+        let mut default = HandCrafted::default();
+        searcher.evaluator.prepare(&state);
+        expected.prepare(&state);
+        default.prepare(&state);
+        let score = searcher.evaluator.evaluate(&state);
+        assert_eq!(score, expected.evaluate(&state));
+        assert_ne!(score, default.evaluate(&state));
+    }
+}
@@ -0,0 +1,208 @@
+use crate::searchinfo::{SearchInfo, SearchListener};
+use game_sdk::{Action, ClientListener, GameState};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{Builder, JoinHandle};
+
+/// The searchers keep their tables inline, which does not fit on the default stack of a thread
+pub const SEARCH_THREAD_STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Implemented by every player that can be stopped from outside of its search
+pub trait Interruptible {
+    /// Flag that is polled by the search. Setting it to true makes the search return as soon as possible
+    fn stop_signal(&self) -> Arc<AtomicBool>;
+
+    /// Listeners that get notified about the progress of the search
+    fn listeners(&mut self) -> &mut Vec<Box<dyn SearchListener>>;
+}
+
+struct BestActionTracker(Arc<Mutex<Option<Action>>>);
+impl SearchListener for BestActionTracker {
+    fn on_search_info(&mut self, info: &SearchInfo) {
+        if let Some(action) = info.best_action() {
+            *self.0.lock().unwrap() = Some(action);
+        }
+    }
+
+    fn on_book_move(&mut self, action: Action) {
+        *self.0.lock().unwrap() = Some(action);
+    }
+}
+
+struct RunningGuard(Arc<AtomicBool>);
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Runs the search of a player on a worker thread.
+/// The player is moved into the handle and given back by `join`
+pub struct SearchHandle<P> {
+    stop_signal: Arc<AtomicBool>,
+    running: Arc<AtomicBool>,
+    best_action: Arc<Mutex<Option<Action>>>,
+    worker: JoinHandle<(P, Action)>,
+}
+
+impl<P: ClientListener + Interruptible + Send + 'static> SearchHandle<P> {
+    pub fn start(player: P, state: &GameState) -> Self {
+        SearchHandle::with_listener(player, state, None)
+    }
+
+    pub fn with_listener(
+        player: P,
+        state: &GameState,
+        listener: Option<Box<dyn SearchListener>>,
+    ) -> Self {
+        SearchHandle::spawn(player, state, listener, None)
+    }
+
+    /// Like `start`, but calls `on_finished` on the search thread once the move is known,
+    /// so the caller can wait for the search without polling `is_running`
+    pub fn with_completion<F: FnOnce() + Send + 'static>(
+        player: P,
+        state: &GameState,
+        on_finished: F,
+    ) -> Self {
+        SearchHandle::spawn(player, state, None, Some(Box::new(on_finished)))
+    }
+
+    fn spawn(
+        mut player: P,
+        state: &GameState,
+        listener: Option<Box<dyn SearchListener>>,
+        on_finished: Option<Box<dyn FnOnce() + Send>>,
+    ) -> Self {
+        let stop_signal = player.stop_signal();
+        stop_signal.store(false, Ordering::SeqCst);
+        let running = Arc::new(AtomicBool::new(true));
+        let best_action = Arc::new(Mutex::new(None));
+
+        let listeners_before = player.listeners().len();
+        player
+            .listeners()
+            .push(Box::new(BestActionTracker(best_action.clone())));
+        if let Some(listener) = listener {
+            player.listeners().push(listener);
+        }
+
+        let state = state.clone();
+        let guard = RunningGuard(running.clone());
+        let worker_best_action = best_action.clone();
+        let worker = Builder::new()
+            .name("search".to_owned())
+            .stack_size(SEARCH_THREAD_STACK_SIZE)
+            .spawn(move || {
+                let _guard = guard;
+                let action = player.on_move_request(&state);
+                player.listeners().truncate(listeners_before);
+                *worker_best_action.lock().unwrap() = Some(action);
+                if let Some(on_finished) = on_finished {
+                    on_finished();
+                }
+                (player, action)
+            })
+            .expect("Could not spawn search thread");
+        SearchHandle {
+            stop_signal,
+            running,
+            best_action,
+            worker,
+        }
+    }
+
+    /// Asks the search to return its current best move as soon as possible
+    pub fn stop(&self) {
+        self.stop_signal.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Best move of the last completed iteration, if any iteration has been completed yet
+    pub fn best_action(&self) -> Option<Action> {
+        *self.best_action.lock().unwrap()
+    }
+
+    /// Waits for the search to finish and returns the player together with the chosen move
+    pub fn join(self) -> (P, Action) {
+        let res = self.worker.join().expect("Search thread panicked");
+        self.stop_signal.store(false, Ordering::SeqCst);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Interruptible, SearchHandle, SEARCH_THREAD_STACK_SIZE};
+    use crate::search::Searcher;
+    use crate::searchlimits::SearchLimits;
+    use crate::timecontrol::Timecontrol;
+    use game_sdk::gamerules::calculate_legal_moves;
+    use game_sdk::{ActionList, GameState};
+    use std::sync::atomic::Ordering;
+    use std::sync::mpsc;
+    use std::thread::{self, Builder};
+    use std::time::Duration;
+
+    const FEN: &str = "20 RED 19807040637789456435240771584 576460752303423488 0 0 0 140737488355328 0 0 0 34359738368 4835703278458585418301440 140737488355328 2417851639229258349412352 8388608 2361183241434822606848 144115188109410304 0 288230376151711744 576460752303423488";
+
+    /// Moving a searcher by value needs more than the default stack of a test thread
+    fn with_large_stack<F: FnOnce() + Send + 'static>(test: F) {
+        Builder::new()
+            .stack_size(SEARCH_THREAD_STACK_SIZE)
+            .spawn(test)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn finished_search_returns_the_player_and_its_move() {
+        with_large_stack(finished_search);
+    }
+
+    #[test]
+    fn stopped_search_returns_its_best_move() {
+        with_large_stack(stopped_search);
+    }
+
+    fn finished_search() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut searcher = Searcher::deterministic(SearchLimits::none().with_depth(3));
+        searcher.listeners.clear();
+        let expected = searcher.search_move(&state);
+        let (sender, receiver) = mpsc::channel();
+        let handle = SearchHandle::with_completion(searcher, &state, move || {
+            sender.send(()).unwrap();
+        });
+        receiver.recv_timeout(Duration::from_secs(60)).unwrap();
+        assert_eq!(handle.best_action(), Some(expected));
+        let (mut searcher, action) = handle.join();
+        assert_eq!(action, expected);
+        // the listeners of the handle are removed again
+        assert!(searcher.listeners().is_empty());
+    }
+
+    fn stopped_search() {
+        let state = GameState::from_fen(FEN.to_owned());
+        let mut searcher = Searcher::with_tc(Timecontrol::Infinite);
+        searcher.listeners.clear();
+        let handle = SearchHandle::start(searcher, &state);
+        while handle.best_action().is_none() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(handle.is_running());
+        handle.stop();
+        let best_action = handle.best_action();
+        let (searcher, action) = handle.join();
+        let mut al = ActionList::default();
+        calculate_legal_moves(&state, &mut al);
+        assert!(al.find_action(action).is_some());
+        assert!(best_action.is_some());
+        // the next search must not stop right away
+        assert!(!searcher.stop_signal().load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,62 @@
+use game_sdk::searchinfo::format_pv;
+pub use game_sdk::searchinfo::{ScoreType, SearchInfo};
+use game_sdk::{Action, GameState};
+
+/// Receives the output of a search, instead of the search printing it directly
+pub trait SearchListener: Send {
+    fn on_search_start(&mut self, _state: &GameState) {}
+
+    /// Called after every completed iteration
+    fn on_search_info(&mut self, info: &SearchInfo);
+
+    /// Called periodically during long iterations
+    fn on_nodes(&mut self, _nodes: u64, _nps: f64) {}
+
+    /// Called once the search returns `action`. `info` is the last emitted info
+    fn on_search_finished(&mut self, _action: Action, _info: &SearchInfo) {}
+
+    /// Called instead of any other callback when the move is taken from the opening book
+    fn on_book_move(&mut self, _action: Action) {}
+
+    /// Called with errors that the player can not return to its caller, e.g. a failed save
+    fn on_error(&mut self, _message: &str) {}
+}
+
+impl<F: FnMut(&SearchInfo) + Send> SearchListener for F {
+    fn on_search_info(&mut self, info: &SearchInfo) {
+        self(info)
+    }
+}
+
+/// Prints everything to stdout in the format expected by the referee
+pub struct InfoPrinter;
+impl SearchListener for InfoPrinter {
+    fn on_search_start(&mut self, state: &GameState) {
+        println!("Searching state w/ fen:{}", state.to_fen());
+    }
+
+    fn on_search_info(&mut self, info: &SearchInfo) {
+        println!("{}", info);
+    }
+
+    fn on_nodes(&mut self, _nodes: u64, nps: f64) {
+        println!("info nps {}", nps);
+    }
+
+    fn on_search_finished(&mut self, action: Action, info: &SearchInfo) {
+        println!(
+            "Finished search with move {:?} and score {}, pv: {}",
+            action,
+            info.score,
+            format_pv(&info.pv)
+        );
+    }
+
+    fn on_book_move(&mut self, action: Action) {
+        println!("Book move {}", action.to_string());
+    }
+
+    fn on_error(&mut self, message: &str) {
+        println!("{}", message);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::search::MAX_SEARCH_DEPTH;
+use crate::searchinfo::SearchInfo;
+
+/// Limits of a search on top of its `Timecontrol`. The search stops as soon as any limit is reached
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SearchLimits {
+    /// Maximum depth in plies. MCTS stops once its principal variation is this long or ends the game
+    pub depth: Option<usize>,
+    /// Maximum amount of nodes. For MCTS these are the visits of the root, including reused ones
+    pub nodes: Option<u64>,
+    /// Stop as soon as a win in at most this many own moves is found. Only used by alpha beta
+    pub mate: Option<usize>,
+    /// Amount of new MCTS iterations per search
+    pub playouts: Option<u64>,
+}
+
+impl SearchLimits {
+    pub fn none() -> Self {
+        SearchLimits::default()
+    }
+
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    pub fn with_nodes(mut self, nodes: u64) -> Self {
+        self.nodes = Some(nodes);
+        self
+    }
+
+    pub fn with_mate(mut self, moves: usize) -> Self {
+        self.mate = Some(moves);
+        self
+    }
+
+    pub fn with_playouts(mut self, playouts: u64) -> Self {
+        self.playouts = Some(playouts);
+        self
+    }
+
+    /// Deepest iteration the alpha beta search is allowed to start
+    pub fn max_depth(&self) -> usize {
+        let mut res = MAX_SEARCH_DEPTH;
+        if let Some(depth) = self.depth {
+            res = res.min(depth);
+        }
+        if let Some(moves) = self.mate {
+            // The game is only decided after blue's move, so a win in n moves takes up to 2n plies
+            res = res.min(2 * moves);
+        }
+        res.max(1)
+    }
+
+    pub fn nodes_reached(&self, nodes: u64) -> bool {
+        matches!(self.nodes, Some(limit) if nodes >= limit)
+    }
+
+    pub fn playouts_reached(&self, playouts: u64) -> bool {
+        matches!(self.playouts, Some(limit) if playouts >= limit)
+    }
+
+    pub fn depth_reached(&self, depth: usize) -> bool {
+        matches!(self.depth, Some(limit) if depth >= limit)
+    }
+
+    pub fn mate_found(&self, score: i16) -> bool {
+        match (self.mate, SearchInfo::mate_from_score(score)) {
+            (Some(moves), Some(plies)) => plies > 0 && plies as usize <= 2 * moves,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SearchLimits;
+    use crate::search::MAX_SEARCH_DEPTH;
+    use game_sdk::MATE_IN_MAX;
+
+    #[test]
+    fn limits_are_reached_at_their_values() {
+        let limits = SearchLimits::none().with_nodes(100).with_playouts(10);
+        assert!(!limits.nodes_reached(99) && limits.nodes_reached(100));
+        assert!(!limits.playouts_reached(9) && limits.playouts_reached(10));
+        assert!(!limits.depth_reached(1000));
+        assert_eq!(limits.max_depth(), MAX_SEARCH_DEPTH);
+        assert_eq!(SearchLimits::none().with_depth(0).max_depth(), 1);
+        // the smaller of depth and mate limit counts
+        let limits = SearchLimits::none().with_depth(5).with_mate(2);
+        assert_eq!(limits.max_depth(), 4);
+        assert!(limits.depth_reached(5));
+    }
+
+    #[test]
+    fn mate_limit_needs_an_own_win() {
+        let limits = SearchLimits::none().with_mate(2);
+        // a win in 3 plies takes 2 own moves, a win in 5 plies 3
+        assert!(limits.mate_found(MATE_IN_MAX + 57));
+        assert!(!limits.mate_found(MATE_IN_MAX + 55));
+        assert!(!limits.mate_found(-(MATE_IN_MAX + 59)));
+        assert!(!limits.mate_found(100));
+        assert!(!SearchLimits::none().mate_found(MATE_IN_MAX + 59));
+    }
+}
@@ -1,13 +1,38 @@
-#[derive(Copy, Clone)]
+/// The official server disqualifies a player that needs longer than this for a move
+pub const SERVER_MOVE_TIME: u64 = 2000;
+/// Time kept back from the server limit for communication and process scheduling
+pub const SAFETY_MARGIN: u64 = 200;
+/// Budget used for a whole game by default, about 1.5 seconds per own move
+pub const DEFAULT_GAME_TIME: u64 = 30 * 1500;
+/// Every move gets at least this much time, even if the budget is used up
+pub const MIN_MOVE_TIME: u64 = 50;
+
+/// Factor the allocated time of a move is scaled with if the best move changes
+const BEST_MOVE_CHANGE_SCALE: f64 = 1.5;
+/// Factor the allocated time of a move is scaled with if the score drops
+const SCORE_DROP_SCALE: f64 = 1.25;
+/// Score drop between two iterations from which on more time is spent
+const SCORE_DROP_THRESHOLD: i16 = 15;
+const MAX_SCALE: f64 = 3.;
+/// Share of the allocated time that is used if one move dominates all others
+const DOMINANT_SCALE: f64 = 0.25;
+/// Share of an iteration that is expected to be spent on the first root move.
+/// The result of an aborted iteration can be used once that move is finished
+const FIRST_MOVE_SHARE: f64 = 0.5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Timecontrol {
     Infinite,
     MoveTime(u64),
+    /// Time in milliseconds that is left for all remaining moves of the game
+    GameTime(u64),
 }
 impl Timecontrol {
     pub fn time_over(&self, elapsed: u64) -> bool {
         match self {
             Timecontrol::Infinite => false,
             Timecontrol::MoveTime(movetime) => elapsed >= *movetime,
+            Timecontrol::GameTime(budget) => elapsed >= *budget,
         }
     }
 
@@ -15,6 +40,141 @@ impl Timecontrol {
         match self {
             Timecontrol::Infinite => 2000,
             Timecontrol::MoveTime(movetime) => *movetime as i64 - elapsed as i64,
+            Timecontrol::GameTime(budget) => *budget as i64 - elapsed as i64,
+        }
+    }
+
+    /// Timecontrol for the next move after `elapsed` milliseconds have been used for this one
+    pub fn spend(self, elapsed: u64) -> Timecontrol {
+        match self {
+            Timecontrol::GameTime(budget) => Timecontrol::GameTime(budget.saturating_sub(elapsed)),
+            tc => tc,
+        }
+    }
+}
+
+/// Decides how long a single move is searched.
+/// With a `GameTime` budget the time is split over the remaining own moves and adapted
+/// to the course of the search, the other timecontrols are followed exactly
+#[derive(Clone, Debug)]
+pub struct TimeManager {
+    pub tc: Timecontrol,
+    /// Time the search should take if nothing special happens
+    pub optimum: u64,
+    /// Time after which the search is aborted, even in the middle of an iteration
+    pub maximum: u64,
+    scale: f64,
+}
+
+impl TimeManager {
+    pub fn new(tc: Timecontrol, ply: u8) -> Self {
+        let (optimum, maximum) = match tc {
+            Timecontrol::Infinite => (u64::MAX, u64::MAX),
+            Timecontrol::MoveTime(movetime) => (movetime, movetime),
+            Timecontrol::GameTime(budget) => {
+                // including this one
+                let own_moves_left = 30 - ply.min(59) as u64 / 2;
+                let hard_cap = SERVER_MOVE_TIME - SAFETY_MARGIN;
+                let optimum = (budget / own_moves_left).max(MIN_MOVE_TIME).min(hard_cap);
+                let maximum = (budget.saturating_sub((own_moves_left - 1) * MIN_MOVE_TIME))
+                    .max(optimum)
+                    .min(hard_cap);
+                (optimum, maximum)
+            }
+        };
+        TimeManager {
+            tc,
+            optimum,
+            maximum,
+            scale: 1.,
+        }
+    }
+
+    /// Time after which no new iteration is started
+    pub fn soft_limit(&self) -> u64 {
+        match self.tc {
+            Timecontrol::GameTime(_) => {
+                ((self.optimum as f64 * self.scale) as u64).min(self.maximum)
+            }
+            _ => self.optimum,
+        }
+    }
+
+    /// Time after which the search is aborted, even in the middle of an iteration
+    pub fn hard_limit(&self) -> u64 {
+        self.maximum
+    }
+
+    /// Hard limit, checked while searching
+    pub fn time_over(&self, elapsed: u64) -> bool {
+        elapsed >= self.hard_limit()
+    }
+
+    /// Time until the soft limit is reached
+    pub fn time_left(&self, elapsed: u64) -> i64 {
+        match self.tc {
+            Timecontrol::GameTime(_) => self.soft_limit() as i64 - elapsed as i64,
+            tc => tc.time_left(elapsed),
+        }
+    }
+
+    /// Checked before every iteration with its predicted duration. An iteration is only
+    /// started before the soft limit and if its first root move can be finished before the hard limit
+    pub fn start_iteration(&self, elapsed: u64, predicted: u64) -> bool {
+        elapsed < self.soft_limit()
+            && elapsed.saturating_add((predicted as f64 * FIRST_MOVE_SHARE) as u64)
+                < self.hard_limit()
+    }
+
+    /// Called after every iteration with the difference of its score to the one of the last iteration
+    pub fn update(&mut self, best_move_changed: bool, score_change: i16) {
+        if best_move_changed {
+            self.scale *= BEST_MOVE_CHANGE_SCALE;
+        }
+        if score_change <= -SCORE_DROP_THRESHOLD {
+            self.scale *= SCORE_DROP_SCALE;
         }
+        self.scale = self.scale.min(MAX_SCALE);
+    }
+
+    /// One move is so much better than all others that searching longer is a waste of time
+    pub fn set_dominant(&mut self) {
+        self.scale = self.scale.min(DOMINANT_SCALE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TimeManager, Timecontrol, SAFETY_MARGIN, SERVER_MOVE_TIME};
+
+    #[test]
+    fn game_time_is_split_over_remaining_moves() {
+        let tm = TimeManager::new(Timecontrol::GameTime(30000), 0);
+        assert_eq!(tm.soft_limit(), 1000);
+        assert_eq!(tm.hard_limit(), SERVER_MOVE_TIME - SAFETY_MARGIN);
+        // blue's last move may use everything that is left, up to the server limit
+        let tm = TimeManager::new(Timecontrol::GameTime(1000), 59);
+        assert_eq!(tm.soft_limit(), 1000);
+        assert_eq!(tm.hard_limit(), 1000);
+    }
+
+    #[test]
+    fn instability_extends_and_dominance_shortens_the_search() {
+        let mut tm = TimeManager::new(Timecontrol::GameTime(30000), 0);
+        assert!(tm.start_iteration(900, 0));
+        tm.update(true, -50);
+        assert!(tm.start_iteration(1700, 0));
+        assert!(tm.time_over(SERVER_MOVE_TIME - SAFETY_MARGIN));
+        tm.set_dominant();
+        assert!(!tm.start_iteration(300, 0));
+    }
+
+    #[test]
+    fn iterations_are_not_started_without_a_chance_to_finish() {
+        let tm = TimeManager::new(Timecontrol::MoveTime(1000), 0);
+        assert!(tm.start_iteration(400, 1000));
+        assert!(!tm.start_iteration(600, 1000));
+        let tm = TimeManager::new(Timecontrol::Infinite, 0);
+        assert!(tm.start_iteration(100_000, 1_000_000));
     }
 }
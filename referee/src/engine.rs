@@ -1,5 +1,6 @@
 use crate::interprocess_communication::{block_on_output, print_command};
 use crate::logging::Log;
+use game_sdk::searchinfo::{parse_nps, ScoreType, SearchInfo};
 use game_sdk::{Action, GameState, MATE_IN_MAX};
 use std::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, Stdio};
 use std::time::Instant;
@@ -67,6 +68,8 @@ pub struct Engine {
     pub disqs: usize,
     pub blunders: usize,
     pub stats: EngineStats,
+    /// Sent as `setoption <name> <value>` before every game
+    pub options: Vec<(String, String)>,
 }
 impl Engine {
     pub fn get_handles(&self) -> (Child, ChildStdin, ChildStdout, ChildStderr) {
@@ -143,11 +146,20 @@ impl Engine {
             disqs: 0,
             blunders: 0,
             stats: EngineStats::default(),
+            options: Vec::new(),
         }
     }
     pub fn set_tc(&self, stdin: &mut ChildStdin, movetime: u64) {
         print_command(stdin, format!("tc {}\n", movetime));
     }
+    pub fn set_nodes(&self, stdin: &mut ChildStdin, nodes: u64) {
+        print_command(stdin, format!("nodes {}\n", nodes));
+    }
+    pub fn set_options(&self, stdin: &mut ChildStdin) {
+        for (name, value) in self.options.iter() {
+            print_command(stdin, format!("setoption {} {}\n", name, value));
+        }
+    }
     pub fn request_move(
         &mut self,
         game_state: &GameState,
@@ -155,7 +167,13 @@ impl Engine {
         stdout: ChildStdout,
         stderr: &mut ChildStderr,
         engine_log: &mut Log,
-    ) -> (Option<Action>, Option<i16>, Option<bool>, ChildStdout) {
+    ) -> (
+        Option<Action>,
+        Option<i16>,
+        Option<ScoreType>,
+        Option<bool>,
+        ChildStdout,
+    ) {
         let request = format!("requestmove {}\n", game_state.to_fen());
         let now = Instant::now();
         print_command(stdin, request);
@@ -172,33 +190,21 @@ impl Engine {
         stats.avg_timeused = elapsed as f64;
         let mut action = None;
         let mut score = None;
+        let mut score_type = None;
         let mut depth = None;
         let lines: Vec<&str> = output.split("\n").collect();
         lines.iter().for_each(|&line| {
             if line.starts_with("bestmove") {
                 let action_str = line.split(" ").collect::<Vec<&str>>()[1..].join(" ");
                 action = Some(Action::from_string(action_str));
-            } else if line.starts_with("info") {
-                let args = line.split(" ").collect::<Vec<&str>>();
-                let mut index = 1;
-                while index < args.len() {
-                    match args[index] {
-                        "nps" => {
-                            stats.avg_nps = args[index + 1].parse::<f64>().unwrap();
-                            index += 2;
-                        }
-                        "depth" => {
-                            stats.avg_depth = args[index + 1].parse::<f64>().unwrap();
-                            depth = Some(args[index + 1].parse::<u8>().unwrap());
-                            index += 2;
-                        }
-                        "score" => {
-                            score = Some(args[index + 1].parse::<i16>().unwrap());
-                            index += 2;
-                        }
-                        _ => index += 1,
-                    }
-                }
+            } else if let Some(info) = SearchInfo::parse(line) {
+                stats.avg_nps = info.nps;
+                stats.avg_depth = info.depth as f64;
+                depth = Some(info.depth as u8);
+                score = Some(info.score);
+                score_type = info.score_type;
+            } else if let Some(nps) = parse_nps(line) {
+                stats.avg_nps = nps;
             }
             ()
         });
@@ -208,7 +214,7 @@ impl Engine {
         } else {
             None
         };
-        (action, score, saw_to_end, stdout)
+        (action, score, score_type, saw_to_end, stdout)
     }
 }
 pub fn get_elo_gain(p_a: f64) -> f64 {
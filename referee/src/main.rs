@@ -4,6 +4,7 @@ use crate::logging::Log;
 use crate::queue::ThreadSafeQueue;
 use game_sdk::bitboard::get_neighbours;
 use game_sdk::gamerules::{calculate_legal_moves, get_result, is_game_finished};
+use game_sdk::searchinfo::ScoreType;
 use game_sdk::{Action, ActionList, Color, GameState, PieceType, MATED_IN_MAX, MATE_IN_MAX};
 use rand::prelude::ThreadRng;
 use rand::Rng;
@@ -18,6 +19,7 @@ mod engine;
 mod interprocess_communication;
 pub mod logging;
 mod queue;
+mod spsa;
 #[derive(Debug)]
 pub struct Config {
     pub threads: usize,
@@ -25,8 +27,13 @@ pub struct Config {
     pub engine1_path: String,
     pub engine2_path: String,
     pub movetime: u64,
+    pub nodes: Option<u64>,
     pub blunder_detection: bool,
     pub blunder_threshold: Option<i16>,
+    /// Parameter file of an SPSA tuning run, which plays `games` iterations instead of a match
+    pub spsa: Option<String>,
+    /// Game pairs per SPSA iteration
+    pub spsa_pairs: usize,
 }
 
 pub struct GameTask {
@@ -36,6 +43,7 @@ pub struct GameTask {
     pub engine1: Engine,
     pub engine2: Engine,
     pub movetime: u64,
+    pub nodes: Option<u64>,
     pub blunder_detection: bool,
     pub blunder_threshold: Option<i16>,
 }
@@ -53,8 +61,11 @@ fn main() {
         engine1_path: "".to_owned(),
         engine2_path: "".to_owned(),
         movetime: 1800,
+        nodes: None,
         blunder_detection: false,
         blunder_threshold: None,
+        spsa: None,
+        spsa_pairs: 1,
     };
     let args: Vec<String> = env::args().collect();
     let mut index = 1;
@@ -86,6 +97,14 @@ fn main() {
                     .expect("Amount of movetime given is not a correct number");
                 index += 2;
             }
+            "-nodes" | "nodes" => {
+                config.nodes = Some(
+                    args[index + 1]
+                        .parse::<u64>()
+                        .expect("Amount of nodes given is not a correct number"),
+                );
+                index += 2;
+            }
             "-blunderdetection" | "blunderdetection" | "-bd" | "bd" => {
                 config.blunder_detection = args[index + 1]
                     .parse::<bool>()
@@ -100,13 +119,27 @@ fn main() {
                 );
                 index += 2;
             }
+            "-spsa" | "spsa" => {
+                config.spsa = Some(args[index + 1].to_owned());
+                index += 2;
+            }
+            "-pairs" | "pairs" => {
+                config.spsa_pairs = args[index + 1]
+                    .parse::<usize>()
+                    .expect("Amount of game pairs given is not a correct number");
+                index += 2;
+            }
             _ => {
                 index += 1;
             }
         }
     }
     //Step2. Game Loop
-    game_loop(config);
+    if let Some(path) = config.spsa.clone() {
+        spsa::spsa_loop(config, &path);
+    } else {
+        game_loop(config);
+    }
 }
 fn game_loop(config: Config) {
     let mut engine1 = Engine::from_path(&config.engine1_path);
@@ -119,6 +152,7 @@ fn game_loop(config: Config) {
             &engine1,
             &engine2,
             config.movetime,
+            config.nodes,
             config.blunder_detection,
             config.blunder_threshold,
         )));
@@ -182,6 +216,7 @@ pub fn load_random_openings(
     engine1: &Engine,
     engine2: &Engine,
     movetime: u64,
+    nodes: Option<u64>,
     blunder_detection: bool,
     blunder_threshold: Option<i16>,
 ) -> Vec<GameTask> {
@@ -222,6 +257,7 @@ pub fn load_random_openings(
             engine1: engine1.clone(),
             engine2: engine2.clone(),
             movetime,
+            nodes,
             blunder_detection,
             blunder_threshold,
         });
@@ -232,6 +268,7 @@ pub fn load_random_openings(
             engine1: engine1.clone(),
             engine2: engine2.clone(),
             movetime,
+            nodes,
             blunder_detection,
             blunder_threshold,
         });
@@ -290,6 +327,12 @@ pub fn play_game(game: GameTask, error_log: Arc<Mutex<Log>>) -> TaskResult {
     );
     engine1.set_tc(&mut e1stdin, game.movetime);
     engine2.set_tc(&mut e2stdin, game.movetime);
+    engine1.set_options(&mut e1stdin);
+    engine2.set_options(&mut e2stdin);
+    if let Some(nodes) = game.nodes {
+        engine1.set_nodes(&mut e1stdin, nodes);
+        engine2.set_nodes(&mut e2stdin, nodes);
+    }
 
     let (mut e1_last_score, mut e1_saw_to_end, mut e2_last_score, mut e2_saw_to_end) =
         (None, None, None, None);
@@ -299,23 +342,35 @@ pub fn play_game(game: GameTask, error_log: Arc<Mutex<Log>>) -> TaskResult {
             || state.color_to_move == Color::BLUE && !game.engine1_is_red;
         let action: Option<Action>;
         let score: Option<i16>;
+        let score_type: Option<ScoreType>;
         let saw_to_end: Option<bool>;
         if is_engine1 {
             let res =
                 engine1.request_move(&state, &mut e1stdin, e1stdout, &mut e1stderr, &mut e1log);
             action = res.0;
             score = res.1;
-            saw_to_end = res.2;
-            e1stdout = res.3;
+            score_type = res.2;
+            saw_to_end = res.3;
+            e1stdout = res.4;
         } else {
             let res =
                 engine2.request_move(&state, &mut e2stdin, e2stdout, &mut e2stderr, &mut e2log);
             action = res.0;
             score = res.1;
-            saw_to_end = res.2;
-            e2stdout = res.3;
+            score_type = res.2;
+            saw_to_end = res.3;
+            e2stdout = res.4;
+        }
+        // the score type tells readers of the log how to interpret the score
+        match score_type {
+            Some(score_type) => fens.push(format!(
+                "{}//{:?} {}",
+                state.to_fen(),
+                score,
+                score_type.token()
+            )),
+            None => fens.push(format!("{}//{:?}", state.to_fen(), score)),
         }
-        fens.push(format!("{}//{:?}", state.to_fen(), score));
         calculate_legal_moves(&state, &mut al);
         if action.is_none() || al.find_action(action.unwrap()).is_none() {
             if is_engine1 {
@@ -0,0 +1,201 @@
+use crate::engine::Engine;
+use crate::logging::Log;
+use crate::queue::ThreadSafeQueue;
+use crate::{
+    load_random_openings, play_game, Config, GameTask, TaskResult, LOG_DIR, REFEREE_ERROR_LOG,
+};
+use rand::Rng;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub const SPSA_LOG: &str = "spsa.log";
+/// Decay of the step sizes, the values recommended by Spall
+const ALPHA: f64 = 0.602;
+const GAMMA: f64 = 0.101;
+
+/// A player option that is tuned. `c_end` is the perturbation and `r_end` the learning
+/// rate at the last iteration, both decay towards them from larger values
+#[derive(Clone, Debug)]
+pub struct SpsaParameter {
+    pub name: String,
+    pub value: f64,
+    pub min: f64,
+    pub max: f64,
+    pub c_end: f64,
+    pub r_end: f64,
+    /// Integer options are rounded before they are sent to the engine
+    pub integer: bool,
+}
+
+impl SpsaParameter {
+    /// Parses `name value min max c_end r_end`
+    pub fn parse(line: &str) -> Result<SpsaParameter, String> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() != 6 {
+            return Err(format!(
+                "Expected 'name value min max c_end r_end', got '{}'",
+                line
+            ));
+        }
+        let mut numbers = [0f64; 5];
+        for (number, token) in numbers.iter_mut().zip(tokens[1..].iter()) {
+            *number = token
+                .parse()
+                .map_err(|_| format!("Invalid number {} in '{}'", token, line))?;
+        }
+        Ok(SpsaParameter {
+            name: tokens[0].to_owned(),
+            value: numbers[0],
+            min: numbers[1],
+            max: numbers[2],
+            c_end: numbers[3],
+            r_end: numbers[4],
+            integer: tokens[1].parse::<i64>().is_ok(),
+        })
+    }
+
+    /// Reads one parameter per line, empty lines and lines starting with `#` are skipped
+    pub fn load(path: &str) -> Result<Vec<SpsaParameter>, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        text.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(SpsaParameter::parse)
+            .collect()
+    }
+
+    fn option_value(&self, value: f64) -> String {
+        let value = value.clamp(self.min, self.max);
+        if self.integer {
+            format!("{}", value.round() as i64)
+        } else {
+            format!("{}", value)
+        }
+    }
+}
+
+/// Plays `tasks` on `threads` threads and returns their results
+fn play_tasks(
+    tasks: Vec<GameTask>,
+    threads: usize,
+    error_log: &Arc<Mutex<Log>>,
+) -> Vec<TaskResult> {
+    let queue = Arc::new(ThreadSafeQueue::new(tasks));
+    let childs: Vec<_> = (0..threads.max(1))
+        .map(|_| {
+            let queue_clone = queue.clone();
+            let log_clone = error_log.clone();
+            thread::spawn(move || {
+                let mut results = Vec::new();
+                while let Some(task) = queue_clone.pop() {
+                    results.push(play_game(task, log_clone.clone()));
+                }
+                results
+            })
+        })
+        .collect();
+    childs
+        .into_iter()
+        .flat_map(|child| child.join().expect("Could not join thread!"))
+        .collect()
+}
+
+/// Tunes the options of `config.engine1_path` by playing `config.spsa_pairs` game pairs
+/// per iteration between two perturbations of the current values, for `config.games` iterations
+pub fn spsa_loop(config: Config, path: &str) {
+    let mut params = SpsaParameter::load(path).unwrap_or_else(|e| panic!("{}", e));
+    let iterations = config.games.max(1);
+    let error_log = Arc::new(Mutex::new(Log::init(
+        &format!("{}{}", LOG_DIR, REFEREE_ERROR_LOG),
+        false,
+    )));
+    let mut spsa_log = Log::init(&format!("{}{}", LOG_DIR, SPSA_LOG), true);
+    let mut log = |line: String| {
+        println!("{}", line);
+        spsa_log.log(&format!("{}\n", line), false);
+    };
+    log(format!(
+        "Starting SPSA with {} iterations of {} game pairs on {}",
+        iterations, config.spsa_pairs, config.engine1_path
+    ));
+    let mut rng = rand::thread_rng();
+    let stability = 0.1 * iterations as f64;
+    let engine = Engine::from_path(&config.engine1_path);
+    for k in 0..iterations {
+        let mut deltas = Vec::with_capacity(params.len());
+        let (mut plus, mut minus) = (engine.clone(), engine.clone());
+        plus.name.push('+');
+        minus.name.push('-');
+        for param in params.iter() {
+            let c_k = param.c_end * (iterations as f64 / (k + 1) as f64).powf(GAMMA);
+            let delta = if rng.gen::<bool>() { c_k } else { -c_k };
+            plus.options
+                .push((param.name.clone(), param.option_value(param.value + delta)));
+            minus
+                .options
+                .push((param.name.clone(), param.option_value(param.value - delta)));
+            deltas.push(delta);
+        }
+        let tasks = load_random_openings(
+            config.spsa_pairs,
+            &plus,
+            &minus,
+            config.movetime,
+            config.nodes,
+            false,
+            None,
+        );
+        for result in play_tasks(tasks, config.threads, &error_log) {
+            plus.add(&result.engine1);
+        }
+        // result of the game pairs from the view of the plus side, between -2 and 2 per pair
+        let result = plus.wins as f64 - plus.losses as f64;
+        let mut line = format!(
+            "Iteration {} result {} (+{} ={} -{})",
+            k + 1,
+            result,
+            plus.wins,
+            plus.draws,
+            plus.losses
+        );
+        for (param, delta) in params.iter_mut().zip(deltas.iter()) {
+            let a_end = param.r_end * param.c_end * param.c_end;
+            let a_k = a_end
+                * ((stability + iterations as f64) / (stability + (k + 1) as f64)).powf(ALPHA);
+            param.value = (param.value + a_k * result / delta).clamp(param.min, param.max);
+            line.push_str(&format!(" {}={:.4}", param.name, param.value));
+        }
+        log(line);
+    }
+    let mut line = "Final values:".to_owned();
+    for param in params.iter() {
+        line.push_str(&format!(
+            " {}={}",
+            param.name,
+            param.option_value(param.value)
+        ));
+    }
+    log(line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpsaParameter;
+
+    #[test]
+    fn parameters_are_parsed_and_clamped() {
+        let reduction = SpsaParameter::parse("nmpreduction 3 1 5 0.5 0.002").unwrap();
+        assert!(reduction.integer);
+        assert_eq!((reduction.min, reduction.max), (1., 5.));
+        // integer options are rounded after clamping
+        assert_eq!(reduction.option_value(3.6), "4");
+        assert_eq!(reduction.option_value(7.), "5");
+        let c_puct = SpsaParameter::parse("cpuct 1.5 0.5 4 0.2 0.002").unwrap();
+        assert!(!c_puct.integer);
+        assert_eq!(c_puct.option_value(0.1), "0.5");
+        assert!(SpsaParameter::parse("cpuct 1.5 0.5 4 0.2").is_err());
+        assert!(SpsaParameter::parse("cpuct 1.5 0.5 x 0.2 0.002").is_err());
+    }
+}
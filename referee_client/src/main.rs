@@ -1,30 +1,189 @@
-use game_sdk::{ClientListener, GameState};
+use game_sdk::GameState;
 use player::mcts::MCTS as Player;
 // use player::search::Searcher as Player;
+use player::options::Configurable;
+use player::searchhandle::SearchHandle;
 use player::timecontrol::Timecontrol;
+use std::collections::VecDeque;
+use std::env;
 use std::io;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
 
-fn main() {
-    let mut searcher = Player::with_tc(Timecontrol::MoveTime(1800));
-    let stdin = io::stdin();
-    let mut line = String::new();
-    loop {
-        line.clear();
-        stdin.read_line(&mut line).ok().unwrap();
+/// Everything the main loop waits for
+enum Event {
+    Command(String),
+    SearchFinished,
+    InputClosed,
+}
+
+/// The player is either idle or moved into the running search
+struct Client {
+    player: Option<Player>,
+    search: Option<SearchHandle<Player>>,
+    /// Commands that arrived during a search, they are handled once it is finished
+    pending: VecDeque<String>,
+    events: Sender<Event>,
+}
+
+impl Client {
+    /// Returns false once the client should exit
+    fn handle_command(&mut self, line: &str) -> bool {
         let arg: Vec<&str> = line.split_whitespace().collect();
-        let cmd = arg[0];
+        let cmd = match arg.first() {
+            Some(cmd) => *cmd,
+            None => return true,
+        };
         match cmd {
-            "exit" | "quit" => break,
+            "exit" | "quit" => {
+                if let Some(handle) = self.search.take() {
+                    handle.stop();
+                    handle.join();
+                }
+                return false;
+            }
+            "stop" => {
+                if let Some(handle) = &self.search {
+                    handle.stop();
+                }
+                return true;
+            }
+            _ => {}
+        }
+        let player = match &mut self.player {
+            Some(player) => player,
+            None => {
+                self.pending.push_back(line.to_owned());
+                return true;
+            }
+        };
+        let res = match cmd {
             "requestmove" | "moverequest" => {
-                let fen = arg[1..].join(" ");
-                let state = GameState::from_fen(fen);
-                let action = searcher.on_move_request(&state);
-                println!("bestmove {}", action.to_string());
+                let state = GameState::from_fen(arg[1..].join(" "));
+                let events = self.events.clone();
+                let player = self.player.take().unwrap();
+                self.search = Some(SearchHandle::with_completion(player, &state, move || {
+                    let _ = events.send(Event::SearchFinished);
+                }));
+                Ok(())
+            }
+            "eval" => {
+                let state = GameState::from_fen(arg[1..].join(" "));
+                println!("{}", state);
+                let evaluator = player.evaluator.as_mut();
+                evaluator.prepare(&state);
+                if let Some(trace) = evaluator.trace(&state) {
+                    println!("{}", trace);
+                }
+                println!(
+                    "{} evaluation for the player to move: {}",
+                    evaluator.name(),
+                    evaluator.evaluate(&state)
+                );
+                Ok(())
+            }
+            "tc" => match arg.get(1) {
+                Some(&"inf") => Ok(Timecontrol::Infinite),
+                Some(&"game") => parse_arg(&arg, 2).map(Timecontrol::GameTime),
+                _ => parse_arg(&arg, 1).map(Timecontrol::MoveTime),
+            }
+            .map(|tc| player.set_tc(tc)),
+            "depth" => parse_arg(&arg, 1).map(|depth| player.limits.depth = Some(depth)),
+            "nodes" => parse_arg(&arg, 1).map(|nodes| player.limits.nodes = Some(nodes)),
+            "mate" => parse_arg(&arg, 1).map(|mate| player.limits.mate = Some(mate)),
+            "playouts" => {
+                parse_arg(&arg, 1).map(|playouts| player.limits.playouts = Some(playouts))
+            }
+            "setoption" if arg.len() >= 3 => player.set_option(arg[1], arg[2]),
+            "newgame" => {
+                player.new_game();
+                Ok(())
+            }
+            _ => Err("unknown command".to_owned()),
+        };
+        if let Err(e) = res {
+            println!("Invalid command {}: {}", line.trim(), e);
+        }
+        true
+    }
+
+    fn finish_search(&mut self) {
+        if let Some(handle) = self.search.take() {
+            let (player, action) = handle.join();
+            println!("bestmove {}", action.to_string());
+            self.player = Some(player);
+        }
+    }
+}
+
+/// Argument `index` of a command, parsed as `T`
+fn parse_arg<T: FromStr>(arg: &[&str], index: usize) -> Result<T, String> {
+    let value = arg.get(index).ok_or("missing argument")?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid argument {}", value))
+}
+
+fn main() {
+    let mut player = Player::with_tc(Timecontrol::MoveTime(1800));
+    // options can be given as arguments, e.g. --hash 16 --evalhash 4
+    let args: Vec<String> = env::args().collect();
+    for option in args[1..].chunks(2) {
+        if let [name, value] = option {
+            let name = name.trim_start_matches('-');
+            if let Err(e) = player.set_option(name, value) {
+                println!("Could not set option {}: {}", name, e);
+            }
+        }
+    }
+
+    // stdin is read on its own thread, so commands like stop arrive while searching
+    let (sender, receiver) = mpsc::channel();
+    let input = sender.clone();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        loop {
+            let mut line = String::new();
+            if stdin.read_line(&mut line).unwrap_or(0) == 0 {
+                let _ = input.send(Event::InputClosed);
+                break;
+            }
+            if input.send(Event::Command(line)).is_err() {
+                break;
+            }
+        }
+    });
+    let mut client = Client {
+        player: Some(player),
+        search: None,
+        pending: VecDeque::new(),
+        events: sender,
+    };
+    let mut input_closed = false;
+    // once stdin is closed, the running search and the queued commands are finished first
+    while !(input_closed && client.search.is_none()) {
+        match receiver.recv() {
+            Ok(Event::Command(line)) => {
+                if !client.handle_command(&line) {
+                    return;
+                }
             }
-            "tc" => {
-                searcher.tc = Timecontrol::MoveTime(arg[1].parse::<u64>().unwrap());
+            Ok(Event::SearchFinished) => {
+                client.finish_search();
+                // stops at the next search, the remaining commands wait for it
+                while client.player.is_some() {
+                    match client.pending.pop_front() {
+                        Some(line) => {
+                            if !client.handle_command(&line) {
+                                return;
+                            }
+                        }
+                        None => break,
+                    }
+                }
             }
-            _ => continue,
+            Ok(Event::InputClosed) | Err(_) => input_closed = true,
         }
     }
 }
@@ -0,0 +1,395 @@
+use crate::{sigmoid, K};
+use game_sdk::gamerules::{get_result, is_game_finished};
+use game_sdk::misc::FenReader;
+use game_sdk::searchinfo::ScoreType;
+use game_sdk::symmetry::obstacle_hash;
+use game_sdk::{Color, GameState};
+use player::evaluation::evaluate;
+use player::evaluator::WIN_PROBABILITY_K;
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+
+pub const DATASET_MAGIC: &[u8; 8] = b"HIVEDATA";
+pub const DATASET_VERSION: u32 = 1;
+/// Search scores at least this large are mates, which say nothing about the evaluation
+pub const MATE_SCORE: i16 = 29900;
+/// Layers of a piece in the dataset file, the piece types followed by the beetle stack levels
+const NUM_LAYERS: u8 = 9;
+
+/// A position of a finished game
+#[derive(Clone)]
+pub struct Position {
+    pub state: GameState,
+    pub result: Option<Color>,
+    /// Score of the search that was made in the position, from red's view
+    pub score: i16,
+}
+
+impl Position {
+    /// Expected result from red's view, `lambda` is the weight of the game result
+    /// and the rest is the win probability of the search score
+    pub fn label(&self, lambda: f64) -> f64 {
+        let result = match self.result {
+            Some(Color::RED) => 1.,
+            Some(Color::BLUE) => 0.,
+            None => 0.5,
+        };
+        lambda * result + (1. - lambda) * sigmoid(K, self.score as f64)
+    }
+}
+
+pub struct DatasetOptions {
+    pub min_ply: u8,
+    pub max_ply: u8,
+    /// Positions whose static evaluation is further than this from the search score are
+    /// dropped, they are usually in the middle of a tactic
+    pub max_disagreement: Option<i16>,
+    /// Treat positions that are symmetric to each other as duplicates
+    pub symmetry: bool,
+    /// Keep as many positions of every game outcome as the rarest outcome has
+    pub balance: bool,
+}
+
+impl Default for DatasetOptions {
+    fn default() -> Self {
+        DatasetOptions {
+            min_ply: 0,
+            max_ply: 60,
+            max_disagreement: None,
+            symmetry: false,
+            balance: false,
+        }
+    }
+}
+
+/// Search score in the units of the evaluation. Win rates in percent are converted
+/// with the logistic curve of the evaluation
+pub fn evaluation_score(score: i16, score_type: ScoreType) -> i16 {
+    match score_type {
+        ScoreType::Evaluation => score,
+        ScoreType::WinRate => {
+            let q = (score as f64 / 100.).clamp(0.001, 0.999);
+            (400. / WIN_PROBABILITY_K * (q / (1. - q)).log10()).round() as i16
+        }
+    }
+}
+
+/// All positions of the finished games of a fen log written by the referee, except for the
+/// final positions and the ones with mate scores. Fails on scores without a score type,
+/// as they can not be told apart from win rates
+pub fn read_fen_log(path: &str) -> io::Result<Vec<Position>> {
+    let mut res = Vec::with_capacity(10000);
+    let mut game = Vec::with_capacity(60);
+    for (line, (state, desc)) in FenReader::from_path(path).into_iter().enumerate() {
+        if is_game_finished(&state) {
+            let result = get_result(&state);
+            res.extend(game.drain(..).map(|(state, score)| Position {
+                state,
+                result,
+                score,
+            }));
+            continue;
+        }
+        let tokens: Vec<&str> = desc.split_whitespace().collect();
+        let score = match tokens.first() {
+            Some(score) => score
+                .replace("Some(", "")
+                .replace(")", "")
+                .parse::<i16>()
+                .ok(),
+            None => None,
+        };
+        let score = match score {
+            // the engine did not report a score
+            None => continue,
+            Some(score) => score,
+        };
+        let score_type = tokens
+            .get(1)
+            .and_then(|&token| ScoreType::from_token(token));
+        let score_type = score_type.ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "the score in line {} of {} has no known score type, the log was written by an older referee",
+                    line + 1,
+                    path
+                ),
+            )
+        })?;
+        if score_type == ScoreType::Evaluation && score.abs() >= MATE_SCORE {
+            continue;
+        }
+        // the search score is from the view of the player to move
+        let score = evaluation_score(score, score_type);
+        let score = if state.color_to_move == Color::RED {
+            score
+        } else {
+            -score
+        };
+        game.push((state, score));
+    }
+    Ok(res)
+}
+
+/// Applies the filters, deduplication and balancing of `options` to `positions`
+pub fn build(positions: Vec<Position>, options: &DatasetOptions) -> Vec<Position> {
+    let mut seen = HashSet::new();
+    let mut by_result: [Vec<Position>; 3] = [Vec::new(), Vec::new(), Vec::new()];
+    for position in positions {
+        let state = &position.state;
+        if state.ply < options.min_ply || state.ply > options.max_ply {
+            continue;
+        }
+        if let Some(max_disagreement) = options.max_disagreement {
+            if (evaluate(state) as i32 - position.score as i32).abs() > max_disagreement as i32 {
+                continue;
+            }
+        }
+        let key = if options.symmetry {
+            state.canonical_hash()
+        } else {
+            state.hash ^ obstacle_hash(state.obstacles)
+        };
+        if !seen.insert(key) {
+            continue;
+        }
+        let outcome = match position.result {
+            Some(Color::RED) => 0,
+            None => 1,
+            Some(Color::BLUE) => 2,
+        };
+        by_result[outcome].push(position);
+    }
+    if options.balance {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let rarest = by_result
+            .iter()
+            .map(|positions| positions.len())
+            .filter(|&len| len > 0)
+            .min()
+            .unwrap_or(0);
+        for positions in by_result.iter_mut() {
+            positions.shuffle(&mut rng);
+            positions.truncate(rarest);
+        }
+    }
+    let [red_wins, draws, blue_wins] = by_result;
+    println!(
+        "Kept {} positions, RedWins: {}, Draws: {}, BlueWins: {}",
+        red_wins.len() + draws.len() + blue_wins.len(),
+        red_wins.len(),
+        draws.len(),
+        blue_wins.len()
+    );
+    red_wins.into_iter().chain(draws).chain(blue_wins).collect()
+}
+
+fn squares(mut bitboard: u128) -> impl Iterator<Item = u8> {
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            return None;
+        }
+        let square = bitboard.trailing_zeros();
+        bitboard ^= 1u128 << square;
+        Some(square as u8)
+    })
+}
+
+/// Every position takes a few bytes for the ply, the side to move, the result and
+/// the score, followed by the squares of the obstacles and of the pieces
+fn write_position<W: Write>(writer: &mut W, position: &Position) -> io::Result<()> {
+    let state = &position.state;
+    let result = match position.result {
+        None => 0,
+        Some(Color::RED) => 1,
+        Some(Color::BLUE) => 2,
+    };
+    writer.write_all(&[state.ply, state.color_to_move as u8 | (result << 1)])?;
+    writer.write_all(&position.score.to_le_bytes())?;
+    let obstacles: Vec<u8> = squares(state.obstacles).collect();
+    writer.write_all(&[obstacles.len() as u8])?;
+    writer.write_all(&obstacles)?;
+    let mut pieces = Vec::with_capacity(2 * 22);
+    for color in 0..2 {
+        let layers = state.pieces.iter().chain(state.beetle_stack.iter());
+        for (layer, bitboards) in layers.enumerate() {
+            for square in squares(bitboards[color]) {
+                pieces.push(square);
+                pieces.push((color as u8) << 4 | layer as u8);
+            }
+        }
+    }
+    writer.write_all(&[(pieces.len() / 2) as u8])?;
+    writer.write_all(&pieces)
+}
+
+fn read_position<R: Read>(reader: &mut R) -> io::Result<Position> {
+    let invalid = |what: &str| io::Error::new(ErrorKind::InvalidData, what.to_owned());
+    let mut header = [0u8; 5];
+    reader.read_exact(&mut header)?;
+    let color_to_move = if header[1] & 1 == 0 {
+        Color::RED
+    } else {
+        Color::BLUE
+    };
+    let result = match header[1] >> 1 {
+        0 => None,
+        1 => Some(Color::RED),
+        2 => Some(Color::BLUE),
+        _ => return Err(invalid("invalid result")),
+    };
+    let score = i16::from_le_bytes([header[2], header[3]]);
+    let mut obstacle_squares = vec![0u8; header[4] as usize];
+    reader.read_exact(&mut obstacle_squares)?;
+    let mut count = [0u8];
+    reader.read_exact(&mut count)?;
+    let mut piece_bytes = vec![0u8; 2 * count[0] as usize];
+    reader.read_exact(&mut piece_bytes)?;
+
+    let mut obstacles = 0u128;
+    for &square in obstacle_squares.iter() {
+        if square >= 121 {
+            return Err(invalid("invalid obstacle"));
+        }
+        obstacles |= 1u128 << square;
+    }
+    let mut pieces = [[0u128; 2]; 5];
+    let mut beetle_stack = [[0u128; 2]; 4];
+    for piece in piece_bytes.chunks(2) {
+        let (square, color, layer) = (piece[0], (piece[1] >> 4) as usize, piece[1] & 15);
+        if square >= 121 || color > 1 || layer >= NUM_LAYERS {
+            return Err(invalid("invalid piece"));
+        }
+        if layer < 5 {
+            pieces[layer as usize][color] |= 1u128 << square;
+        } else {
+            beetle_stack[layer as usize - 5][color] |= 1u128 << square;
+        }
+    }
+    Ok(Position {
+        state: GameState::from_bitboards(header[0], color_to_move, obstacles, pieces, beetle_stack),
+        result,
+        score,
+    })
+}
+
+pub fn save(path: &str, positions: &[Position]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(DATASET_MAGIC)?;
+    writer.write_all(&DATASET_VERSION.to_le_bytes())?;
+    writer.write_all(&(positions.len() as u64).to_le_bytes())?;
+    for position in positions.iter() {
+        write_position(&mut writer, position)?;
+    }
+    writer.flush()
+}
+
+/// Reads a dataset written by `save`, or the positions of a fen log if the file is no dataset
+pub fn load(path: &str) -> io::Result<Vec<Position>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 8];
+    if reader.read_exact(&mut magic).is_err() || &magic != DATASET_MAGIC {
+        return read_fen_log(path);
+    }
+    let mut version = [0u8; 4];
+    reader.read_exact(&mut version)?;
+    if u32::from_le_bytes(version) != DATASET_VERSION {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "dataset version is {}, expected {}",
+                u32::from_le_bytes(version),
+                DATASET_VERSION
+            ),
+        ));
+    }
+    let mut count = [0u8; 8];
+    reader.read_exact(&mut count)?;
+    let count = u64::from_le_bytes(count) as usize;
+    let mut res = Vec::with_capacity(count);
+    for _ in 0..count {
+        res.push(read_position(&mut reader)?);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluation_score, load, read_fen_log, save, Position};
+    use game_sdk::gamerules::{calculate_legal_moves, is_game_finished};
+    use game_sdk::searchinfo::ScoreType;
+    use game_sdk::{ActionList, Color, GameState};
+
+    #[test]
+    fn dataset_roundtrip() {
+        let mut state = GameState::new();
+        state.obstacles = (1u128 << 5) | (1u128 << 49) | (1u128 << 107);
+        let mut al = ActionList::default();
+        let mut positions = Vec::new();
+        for ply in 0..30 {
+            calculate_legal_moves(&state, &mut al);
+            state.make_action(al[al.size / 2]);
+            let result = [None, Some(Color::RED), Some(Color::BLUE)][ply % 3];
+            positions.push(Position {
+                state: state.clone(),
+                result,
+                score: ply as i16 * 37 - 500,
+            });
+        }
+        let path = std::env::temp_dir().join(format!("dataset-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        save(path, &positions).unwrap();
+        let loaded = load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.len(), positions.len());
+        for (loaded, position) in loaded.iter().zip(positions.iter()) {
+            assert!(loaded.state == position.state);
+            assert_eq!(loaded.result, position.result);
+            assert_eq!(loaded.score, position.score);
+        }
+    }
+
+    #[test]
+    fn fen_log_scores_are_converted() {
+        let mut state = GameState::new();
+        let mut al = ActionList::default();
+        let mut lines = Vec::new();
+        while !is_game_finished(&state) {
+            let score = if state.color_to_move == Color::RED {
+                "Some(120) eval"
+            } else {
+                "Some(75) winrate"
+            };
+            lines.push(format!("{}//{}", state.to_fen(), score));
+            calculate_legal_moves(&state, &mut al);
+            state.make_action(al[0]);
+        }
+        lines.push(format!("{}//GameOver", state.to_fen()));
+        let path = std::env::temp_dir().join(format!("fens-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, lines.join("\n")).unwrap();
+        let positions = read_fen_log(path).unwrap();
+        assert_eq!(positions.len(), lines.len() - 1);
+        // red moves with evaluation scores, blue with win rates
+        let blue_score = -evaluation_score(75, ScoreType::WinRate);
+        assert!(blue_score < -100);
+        for position in positions.iter() {
+            let expected = if position.state.color_to_move == Color::RED {
+                120
+            } else {
+                blue_score
+            };
+            assert_eq!(position.score, expected);
+        }
+        // logs of older referees do not tell which engine wrote the scores
+        lines[0] = lines[0].replace(" eval", "");
+        std::fs::write(path, lines.join("\n")).unwrap();
+        assert!(read_fen_log(path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,8 +1,21 @@
-use game_sdk::misc::FenReader;
+mod dataset;
+mod nnue;
+mod selfplay;
+mod texel;
+
 use game_sdk::{Color, GameState};
-use player::evaluation::{evaluate, evaluate_with_parameters, EvaluationParameters};
+use player::evaluation::{
+    evaluate, evaluate_trace, EvalTrace, EvaluationParameters, DEFAULT, NUM_PARAMETERS,
+};
+use player::evaluator::WIN_PROBABILITY_K;
+use player::searchlimits::SearchLimits;
+use std::env;
 
-pub const K: f64 = 1.5;
+/// Tuned parameters are only consistent with the search if they use the same K as the player
+pub const K: f64 = WIN_PROBABILITY_K;
+/// The best parameters of `local_parameter_search` are written here after every iteration and
+/// the ones of the gradient tuning at its end, in the format the player reads with its `evalparams` option
+pub const TUNED_PARAMETERS_PATH: &str = "./tuned_parameters.txt";
 pub struct Dataset(Vec<LabelledGameState>);
 pub fn sigmoid(k: f64, x: f64) -> f64 {
     1. / (1. + 10f64.powf(-k * x / 400.0))
@@ -17,45 +30,40 @@ impl Dataset {
         res
     }
 
-    pub fn get_mean_evaluation_error_with_params(&self, k: f64, params: [f64; 10]) -> f64 {
+    pub fn get_mean_evaluation_error_with_params(&self, k: f64, params: [f64; NUM_PARAMETERS]) -> f64 {
         let params = EvaluationParameters::from_array(params);
         let mut res = 0.;
         for lgs in self.0.iter() {
-            res += (lgs.1 - sigmoid(k, evaluate_with_parameters(&lgs.0, &params) as f64)).powf(2.);
+            res += (lgs.1 - sigmoid(k, lgs.2.score_with_parameters(&params))).powf(2.);
         }
         res /= self.0.len() as f64;
         res
     }
 }
-pub struct LabelledGameState(GameState, f64);
+/// The trace holds the features of the state, so other parameters can be tried without evaluating it again
+pub struct LabelledGameState(GameState, f64, EvalTrace);
 impl LabelledGameState {
-    pub fn from_path(path: &str) -> Dataset {
-        let mut res = Vec::with_capacity(10000);
-        let (mut games, mut redwins, mut draws, mut bluewins) = (0, 0, 0, 0);
-        for game in FenReader::from_path(path).into_game_reader() {
-            let game_res = game.1.clone();
-            game.0.into_iter().for_each(|state| {
-                let val = if game_res == Some(Color::RED) {
-                    1.0
-                } else if game_res == Some(Color::BLUE) {
-                    0.0
-                } else {
-                    0.5
-                };
-                res.push(LabelledGameState(state, val))
-            });
-            if game_res == Some(Color::RED) {
-                redwins += 1;
-            } else if game_res == Some(Color::BLUE) {
-                bluewins += 1;
-            } else {
-                draws += 1;
-            }
-            games += 1;
-        }
+    /// Loads a dataset file or a fen log. `lambda` is the weight of the game results in the
+    /// labels, the rest comes from the search scores
+    pub fn from_path(path: &str, lambda: f64) -> Dataset {
+        let positions =
+            dataset::load(path).unwrap_or_else(|e| panic!("Could not load {}: {}", path, e));
+        let (mut redwins, mut draws, mut bluewins) = (0, 0, 0);
+        let res: Vec<LabelledGameState> = positions
+            .into_iter()
+            .map(|position| {
+                match position.result {
+                    Some(Color::RED) => redwins += 1,
+                    Some(Color::BLUE) => bluewins += 1,
+                    None => draws += 1,
+                }
+                let val = position.label(lambda);
+                let trace = evaluate_trace(&position.state, &DEFAULT);
+                LabelledGameState(position.state, val, trace)
+            })
+            .collect();
         println!(
-            "Parsed {} games with {} states!\nRedWins: {}, Draws: {}, BlueWins: {}",
-            games,
+            "Parsed {} states!\nRedWins: {}, Draws: {}, BlueWins: {}",
             res.len(),
             redwins,
             draws,
@@ -112,14 +120,196 @@ fn local_parameter_search(labelled_states: &Dataset) {
             "finished iteration {} with error {}, params {:?}",
             iteration, best_error, best_params
         );
+        if let Err(e) = EvaluationParameters::from_array(best_params).save(TUNED_PARAMETERS_PATH) {
+            println!("Could not save {}: {}", TUNED_PARAMETERS_PATH, e);
+        }
     }
 }
 
+/// `tuning nnue <out> [-epochs N] [-hidden N] [-lr X] [-lambda X] [data]` trains a network
+/// on the labels of a dataset file or fen log
+fn train_nnue(args: &[String]) {
+    let mut options = nnue::TrainingOptions::default();
+    let mut path = "./referee_logs/fens.txt".to_owned();
+    let mut lambda = 1.;
+    let mut index = 1;
+    while index < args.len() {
+        let value = || args.get(index + 1).expect("Option without value");
+        match args[index].as_str() {
+            "-epochs" => options.epochs = value().parse().expect("Invalid amount of epochs"),
+            "-hidden" => options.hidden_size = value().parse().expect("Invalid hidden size"),
+            "-lr" => options.learning_rate = value().parse().expect("Invalid learning rate"),
+            "-lambda" => lambda = value().parse().expect("Invalid lambda"),
+            fens => {
+                path = fens.to_owned();
+                index += 1;
+                continue;
+            }
+        }
+        index += 2;
+    }
+    let labelled_states = LabelledGameState::from_path(&path, lambda);
+    let network = nnue::train(&labelled_states, &options).quantize();
+    network
+        .save(&args[0])
+        .unwrap_or_else(|e| panic!("Could not save {}: {}", args[0], e));
+    println!("Saved network to {}", args[0]);
+}
+
+/// `tuning [-iterations N] [-lr X] [-validation X] [-threads N] [-lambda X] [data]`
+/// fits the evaluation parameters with gradient descent
+fn gradient_tuning(args: &[String]) {
+    let mut options = texel::TexelOptions::default();
+    let mut path = "./referee_logs/fens.txt".to_owned();
+    let mut lambda = 1.;
+    let mut index = 0;
+    while index < args.len() {
+        let value = || args.get(index + 1).expect("Option without value");
+        match args[index].as_str() {
+            "-iterations" => {
+                options.iterations = value().parse().expect("Invalid amount of iterations")
+            }
+            "-lr" => options.learning_rate = value().parse().expect("Invalid learning rate"),
+            "-validation" => {
+                options.validation_share = value().parse().expect("Invalid validation share")
+            }
+            "-threads" => options.threads = value().parse().expect("Invalid amount of threads"),
+            "-lambda" => lambda = value().parse().expect("Invalid lambda"),
+            fens => {
+                path = fens.to_owned();
+                index += 1;
+                continue;
+            }
+        }
+        index += 2;
+    }
+    let labelled_states = LabelledGameState::from_path(&path, lambda);
+    let params = texel::tune(&labelled_states, &options);
+    print!("Parameters:\n{}", params);
+    if let Err(e) = params.save(TUNED_PARAMETERS_PATH) {
+        println!("Could not save {}: {}", TUNED_PARAMETERS_PATH, e);
+    }
+}
+
+/// `tuning dataset <out> [-minply N] [-maxply N] [-disagreement N] [-symmetry] [-balance] [fens...]`
+/// writes the filtered positions of fen logs or dataset files to a dataset file
+fn build_dataset(args: &[String]) {
+    let mut options = dataset::DatasetOptions::default();
+    let mut paths = Vec::new();
+    let mut index = 1;
+    while index < args.len() {
+        let value = || args.get(index + 1).expect("Option without value");
+        match args[index].as_str() {
+            "-minply" => options.min_ply = value().parse().expect("Invalid ply"),
+            "-maxply" => options.max_ply = value().parse().expect("Invalid ply"),
+            "-disagreement" => {
+                options.max_disagreement = Some(value().parse().expect("Invalid disagreement"))
+            }
+            "-symmetry" => {
+                options.symmetry = true;
+                index += 1;
+                continue;
+            }
+            "-balance" => {
+                options.balance = true;
+                index += 1;
+                continue;
+            }
+            fens => {
+                paths.push(fens.to_owned());
+                index += 1;
+                continue;
+            }
+        }
+        index += 2;
+    }
+    if paths.is_empty() {
+        paths.push("./referee_logs/fens.txt".to_owned());
+    }
+    let mut positions = Vec::new();
+    for path in paths.iter() {
+        positions.extend(
+            dataset::load(path).unwrap_or_else(|e| panic!("Could not load {}: {}", path, e)),
+        );
+    }
+    println!("Read {} positions", positions.len());
+    let positions = dataset::build(positions, &options);
+    dataset::save(&args[0], &positions)
+        .unwrap_or_else(|e| panic!("Could not save {}: {}", args[0], e));
+    println!("Saved dataset to {}", args[0]);
+}
+
+/// `tuning selfplay <out> [-games N] [-threads N] [-nodes N] [-depth N] [-playouts N] [-mcts]
+/// [-randomplies N] [-adjudication N] [-seed N] [-hash MB] [-evalhash MB]` plays games between
+/// in-process players and writes their positions to a dataset file
+fn generate_selfplay(args: &[String]) {
+    let mut options = selfplay::SelfplayOptions::default();
+    let mut limits = SearchLimits::none();
+    let mut index = 1;
+    while index < args.len() {
+        let value = || args.get(index + 1).expect("Option without value");
+        match args[index].as_str() {
+            "-games" => options.games = value().parse().expect("Invalid amount of games"),
+            "-threads" => options.threads = value().parse().expect("Invalid amount of threads"),
+            "-nodes" => limits = limits.with_nodes(value().parse().expect("Invalid nodes")),
+            "-depth" => limits = limits.with_depth(value().parse().expect("Invalid depth")),
+            "-playouts" => {
+                limits = limits.with_playouts(value().parse().expect("Invalid playouts"))
+            }
+            "-randomplies" => options.random_plies = value().parse().expect("Invalid plies"),
+            "-adjudication" => {
+                options.adjudication_score = value().parse().expect("Invalid adjudication score")
+            }
+            "-seed" => options.seed = value().parse().expect("Invalid seed"),
+            "-hash" => options.hash_size = value().parse().expect("Invalid hash size"),
+            "-evalhash" => options.eval_hash_size = value().parse().expect("Invalid hash size"),
+            "-mcts" => {
+                options.mcts = true;
+                index += 1;
+                continue;
+            }
+            other => panic!("Unknown option {}", other),
+        }
+        index += 2;
+    }
+    if options.mcts && limits.depth.is_some() && limits.nodes.is_none() && limits.playouts.is_none()
+    {
+        // the principal variation of MCTS may grow too slowly to ever reach the depth,
+        // so the default node limit is kept as well
+        limits.nodes = options.limits.nodes;
+    }
+    if limits.nodes.is_some() || limits.depth.is_some() || limits.playouts.is_some() {
+        options.limits = limits;
+    }
+    let positions = selfplay::selfplay(options);
+    let positions = dataset::build(positions, &dataset::DatasetOptions::default());
+    dataset::save(&args[0], &positions)
+        .unwrap_or_else(|e| panic!("Could not save {}: {}", args[0], e));
+    println!("Saved dataset to {}", args[0]);
+}
+
 fn main() {
-    let labelled_states = LabelledGameState::from_path("./referee_logs/fens.txt");
-    println!(
-        "Average evaluation error: {}",
-        labelled_states.get_mean_evaluation_error(K)
-    );
-    local_parameter_search(&labelled_states);
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() >= 2 && args[0] == "selfplay" {
+        generate_selfplay(&args[1..]);
+        return;
+    }
+    if args.len() >= 2 && args[0] == "dataset" {
+        build_dataset(&args[1..]);
+        return;
+    }
+    if args.len() >= 2 && args[0] == "nnue" {
+        train_nnue(&args[1..]);
+        return;
+    }
+    if !args.is_empty() && args[0] == "local" {
+        let labelled_states = LabelledGameState::from_path("./referee_logs/fens.txt", 1.);
+        println!(
+            "Average evaluation error: {}",
+            labelled_states.get_mean_evaluation_error(K)
+        );
+        local_parameter_search(&labelled_states);
+        return;
+    }
+    gradient_tuning(&args);
 }
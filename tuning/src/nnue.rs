@@ -0,0 +1,246 @@
+use crate::{sigmoid, Dataset, K};
+use game_sdk::Color;
+use player::nnue::{active_features, Network, EVAL_SCALE, NUM_FEATURES, QA, QB};
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+pub struct TrainingOptions {
+    pub hidden_size: usize,
+    pub epochs: usize,
+    pub batch_size: usize,
+    pub learning_rate: f32,
+}
+
+impl Default for TrainingOptions {
+    fn default() -> Self {
+        TrainingOptions {
+            hidden_size: player::nnue::DEFAULT_HIDDEN_SIZE,
+            epochs: 30,
+            batch_size: 1024,
+            learning_rate: 0.001,
+        }
+    }
+}
+
+/// Active features of the side to move followed by the ones of the other side
+struct Sample {
+    features: [Vec<u16>; 2],
+    red_to_move: bool,
+    result: f32,
+}
+
+/// The network of `player::nnue` in floating point. All parameters are kept in one vector,
+/// the feature weights first, then the feature bias, the output weights and the output bias
+pub struct FloatNetwork {
+    pub hidden_size: usize,
+    pub params: Vec<f32>,
+}
+
+impl FloatNetwork {
+    pub fn random<R: Rng>(hidden_size: usize, rng: &mut R) -> FloatNetwork {
+        let mut network = FloatNetwork {
+            hidden_size,
+            params: vec![0.; (NUM_FEATURES + 3) * hidden_size + 1],
+        };
+        for weight in network.params[..NUM_FEATURES * hidden_size].iter_mut() {
+            *weight = rng.gen_range(-0.1, 0.1);
+        }
+        let output_weights = network.output_weights_start();
+        for weight in network.params[output_weights..output_weights + 2 * hidden_size].iter_mut() {
+            *weight = rng.gen_range(-0.1, 0.1);
+        }
+        network
+    }
+
+    fn bias_start(&self) -> usize {
+        NUM_FEATURES * self.hidden_size
+    }
+
+    fn output_weights_start(&self) -> usize {
+        (NUM_FEATURES + 1) * self.hidden_size
+    }
+
+    fn output_bias_index(&self) -> usize {
+        self.params.len() - 1
+    }
+
+    /// Hidden layers before the activation, returns the output from the side to move's view
+    fn forward(&self, sample: &Sample, accumulators: &mut [Vec<f32>; 2]) -> f32 {
+        let hidden = self.hidden_size;
+        let bias = &self.params[self.bias_start()..][..hidden];
+        let output_weights = &self.params[self.output_weights_start()..][..2 * hidden];
+        let mut output = self.params[self.output_bias_index()];
+        for side in 0..2 {
+            let accumulator = &mut accumulators[side];
+            accumulator.clear();
+            accumulator.extend_from_slice(bias);
+            for &feature in sample.features[side].iter() {
+                let weights = &self.params[feature as usize * hidden..][..hidden];
+                for (value, weight) in accumulator.iter_mut().zip(weights.iter()) {
+                    *value += weight;
+                }
+            }
+            for (value, weight) in accumulator
+                .iter()
+                .zip(output_weights[side * hidden..].iter())
+            {
+                output += value.clamp(0., 1.) * weight;
+            }
+        }
+        output
+    }
+
+    /// Adds the gradient of the squared error of `sample` to `gradient` and returns the error
+    fn backward(
+        &self,
+        sample: &Sample,
+        accumulators: &mut [Vec<f32>; 2],
+        gradient: &mut [f32],
+    ) -> f32 {
+        let hidden = self.hidden_size;
+        let output = self.forward(sample, accumulators);
+        let sign = if sample.red_to_move { 1. } else { -1. };
+        let prediction = sigmoid(K, (sign * output * EVAL_SCALE as f32) as f64) as f32;
+        let error = prediction - sample.result;
+        let output_gradient = 2. * error * prediction * (1. - prediction) * 10f32.ln() * K as f32
+            / 400.
+            * EVAL_SCALE as f32
+            * sign;
+
+        let output_bias = self.output_bias_index();
+        gradient[output_bias] += output_gradient;
+        let output_weights = self.output_weights_start();
+        let bias = self.bias_start();
+        for (side, accumulator) in accumulators.iter().enumerate() {
+            for (j, &value) in accumulator.iter().enumerate() {
+                let weight_index = output_weights + side * hidden + j;
+                gradient[weight_index] += output_gradient * value.clamp(0., 1.);
+                // the clipped activation has no gradient outside of (0, 1)
+                if value <= 0. || value >= 1. {
+                    continue;
+                }
+                let hidden_gradient = output_gradient * self.params[weight_index];
+                gradient[bias + j] += hidden_gradient;
+                for &feature in sample.features[side].iter() {
+                    gradient[feature as usize * hidden + j] += hidden_gradient;
+                }
+            }
+        }
+        error * error
+    }
+
+    pub fn quantize(&self) -> Network {
+        let hidden = self.hidden_size;
+        let quantize = |value: f32, scale: i32| {
+            (value * scale as f32)
+                .round()
+                .max(i16::MIN as f32)
+                .min(i16::MAX as f32) as i16
+        };
+        let mut network = Network::zeroed(hidden);
+        for (quantized, value) in network
+            .feature_weights
+            .iter_mut()
+            .zip(self.params[..NUM_FEATURES * hidden].iter())
+        {
+            *quantized = quantize(*value, QA);
+        }
+        for (quantized, value) in network
+            .feature_bias
+            .iter_mut()
+            .zip(self.params[self.bias_start()..].iter())
+        {
+            *quantized = quantize(*value, QA);
+        }
+        for (quantized, value) in network
+            .output_weights
+            .iter_mut()
+            .zip(self.params[self.output_weights_start()..].iter())
+        {
+            *quantized = quantize(*value, QB);
+        }
+        network.output_bias =
+            (self.params[self.output_bias_index()] * (QA * QB) as f32).round() as i32;
+        network
+    }
+}
+
+struct Adam {
+    m: Vec<f32>,
+    v: Vec<f32>,
+    t: i32,
+}
+
+impl Adam {
+    const BETA1: f32 = 0.9;
+    const BETA2: f32 = 0.999;
+    const EPSILON: f32 = 1e-8;
+
+    fn new(size: usize) -> Adam {
+        Adam {
+            m: vec![0.; size],
+            v: vec![0.; size],
+            t: 0,
+        }
+    }
+
+    fn step(&mut self, params: &mut [f32], gradient: &[f32], learning_rate: f32) {
+        self.t += 1;
+        let correction1 = 1. - Adam::BETA1.powi(self.t);
+        let correction2 = 1. - Adam::BETA2.powi(self.t);
+        for i in 0..params.len() {
+            self.m[i] = Adam::BETA1 * self.m[i] + (1. - Adam::BETA1) * gradient[i];
+            self.v[i] = Adam::BETA2 * self.v[i] + (1. - Adam::BETA2) * gradient[i] * gradient[i];
+            params[i] -= learning_rate * (self.m[i] / correction1)
+                / ((self.v[i] / correction2).sqrt() + Adam::EPSILON);
+        }
+    }
+}
+
+/// Trains a network on the game results of `dataset`, on the CPU only
+pub fn train(dataset: &Dataset, options: &TrainingOptions) -> FloatNetwork {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let to_u16 = |features: Vec<usize>| features.into_iter().map(|f| f as u16).collect();
+    let mut samples: Vec<Sample> = dataset
+        .0
+        .iter()
+        .map(|lgs| {
+            let state = &lgs.0;
+            Sample {
+                features: [
+                    to_u16(active_features(state, state.color_to_move)),
+                    to_u16(active_features(state, state.color_to_move.swap())),
+                ],
+                red_to_move: state.color_to_move == Color::RED,
+                result: lgs.1 as f32,
+            }
+        })
+        .collect();
+    let mut network = FloatNetwork::random(options.hidden_size, &mut rng);
+    let mut adam = Adam::new(network.params.len());
+    let mut gradient = vec![0.; network.params.len()];
+    let mut accumulators = [Vec::new(), Vec::new()];
+    for epoch in 1..=options.epochs {
+        samples.shuffle(&mut rng);
+        let mut error = 0.;
+        for batch in samples.chunks(options.batch_size) {
+            for g in gradient.iter_mut() {
+                *g = 0.;
+            }
+            for sample in batch.iter() {
+                error += network.backward(sample, &mut accumulators, &mut gradient) as f64;
+            }
+            for g in gradient.iter_mut() {
+                *g /= batch.len() as f32;
+            }
+            adam.step(&mut network.params, &gradient, options.learning_rate);
+        }
+        println!(
+            "Epoch {}, average error {}",
+            epoch,
+            error / samples.len() as f64
+        );
+    }
+    network
+}
@@ -0,0 +1,238 @@
+use crate::dataset::{evaluation_score, Position, MATE_SCORE};
+use game_sdk::gamerules::{calculate_legal_moves, get_result, is_game_finished};
+use game_sdk::{ActionList, ClientListener, Color, GameState};
+use player::mcts::MCTS;
+use player::options::Configurable;
+use player::search::Searcher;
+use player::searchhandle::{Interruptible, SEARCH_THREAD_STACK_SIZE};
+use player::searchinfo::{ScoreType, SearchInfo};
+use player::searchlimits::SearchLimits;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::Builder;
+
+pub struct SelfplayOptions {
+    pub games: usize,
+    pub threads: usize,
+    /// Plays with `MCTS` instead of the alpha beta `Searcher`
+    pub mcts: bool,
+    /// Limits of every search, should be a node, playout or depth limit.
+    /// `MCTS` players also need a node or playout limit
+    pub limits: SearchLimits,
+    /// Random moves made after the obstacles are placed, so the games differ
+    pub random_plies: usize,
+    /// A game is adjudicated once the search scores are at least this large for the same side ...
+    pub adjudication_score: i16,
+    /// ... in this many consecutive plies
+    pub adjudication_plies: usize,
+    /// The opening of every game is drawn from this seed and the number of the game,
+    /// so the same options give the same games
+    pub seed: u64,
+    /// Transposition table size of every `Searcher` in MB, it is cleared before every search
+    pub hash_size: usize,
+    /// Evaluation cache size of every `Searcher` in MB, it is cleared before every search
+    pub eval_hash_size: usize,
+}
+
+impl Default for SelfplayOptions {
+    fn default() -> Self {
+        SelfplayOptions {
+            games: 100,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            mcts: false,
+            limits: SearchLimits::none().with_nodes(20000),
+            random_plies: 4,
+            adjudication_score: 1500,
+            adjudication_plies: 4,
+            seed: 0,
+            hash_size: 4,
+            eval_hash_size: 1,
+        }
+    }
+}
+
+/// Random obstacles followed by random moves, without a finished game
+fn random_opening(random_plies: usize, seed: u64) -> GameState {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut al = ActionList::default();
+    loop {
+        let mut state = GameState::random_with(&mut rng);
+        for _ in 0..random_plies {
+            calculate_legal_moves(&state, &mut al);
+            state.make_action(al[rng.gen_range(0, al.size)]);
+            if is_game_finished(&state) {
+                break;
+            }
+        }
+        if !is_game_finished(&state) {
+            return state;
+        }
+    }
+}
+
+/// Plays game number `game` from its random opening and returns its positions with the search scores
+fn play_game<P: ClientListener + Interruptible + Configurable>(
+    player: &mut P,
+    last_info: &Arc<Mutex<SearchInfo>>,
+    options: &SelfplayOptions,
+    game: usize,
+) -> Vec<Position> {
+    player.new_game();
+    let seed = options.seed.wrapping_add(game as u64);
+    let mut state = random_opening(options.random_plies, seed);
+    let mut scores: Vec<(GameState, i16)> = Vec::with_capacity(60);
+    let mut result = None;
+    // plies in a row in which the same side was winning by at least the adjudication score
+    let (mut winning_side, mut winning_plies) = (None, 0);
+    while !is_game_finished(&state) {
+        *last_info.lock().unwrap() = SearchInfo::default();
+        let action = player.on_move_request(&state);
+        let score = {
+            let info = last_info.lock().unwrap();
+            // a search that emits no info, e.g. with a single legal move, counts as even
+            evaluation_score(info.score, info.score_type.unwrap_or(ScoreType::Evaluation))
+        };
+        let red_score = if state.color_to_move == Color::RED {
+            score
+        } else {
+            -score
+        };
+        let leader = if red_score >= options.adjudication_score {
+            Some(Color::RED)
+        } else if red_score <= -options.adjudication_score {
+            Some(Color::BLUE)
+        } else {
+            None
+        };
+        winning_plies = if leader.is_some() && leader == winning_side {
+            winning_plies + 1
+        } else {
+            1
+        };
+        winning_side = leader;
+        if score.abs() >= MATE_SCORE {
+            result = leader;
+            break;
+        }
+        scores.push((state.clone(), red_score));
+        if leader.is_some() && winning_plies >= options.adjudication_plies {
+            result = leader;
+            break;
+        }
+        state.make_action(action);
+        if is_game_finished(&state) {
+            result = get_result(&state);
+        }
+    }
+    scores
+        .into_iter()
+        .map(|(state, score)| Position {
+            state,
+            result,
+            score,
+        })
+        .collect()
+}
+
+fn play_games<P: ClientListener + Interruptible + Configurable>(
+    mut player: P,
+    options: &SelfplayOptions,
+    next_game: &AtomicUsize,
+) -> Vec<(usize, Vec<Position>)> {
+    let last_info = Arc::new(Mutex::new(SearchInfo::default()));
+    let recorder = last_info.clone();
+    player.listeners().clear();
+    player.listeners().push(Box::new(move |info: &SearchInfo| {
+        *recorder.lock().unwrap() = info.clone()
+    }));
+    let mut res = Vec::new();
+    loop {
+        let game = next_game.fetch_add(1, Ordering::SeqCst);
+        if game >= options.games {
+            return res;
+        }
+        let positions = play_game(&mut player, &last_info, options, game);
+        let result = match positions.first().and_then(|position| position.result) {
+            Some(color) => format!("{:?} won", color),
+            None => "draw".to_owned(),
+        };
+        println!(
+            "Game {} finished, {}, {} positions",
+            game + 1,
+            result,
+            positions.len()
+        );
+        res.push((game, positions));
+    }
+}
+
+/// Plays `options.games` games between in-process players on `options.threads` threads.
+/// The positions are ordered by game, so they do not depend on the amount of threads
+pub fn selfplay(options: SelfplayOptions) -> Vec<Position> {
+    let options = Arc::new(options);
+    let next_game = Arc::new(AtomicUsize::new(0));
+    let handles: Vec<_> = (0..options.threads.max(1))
+        .map(|_| {
+            let (options, next_game) = (options.clone(), next_game.clone());
+            Builder::new()
+                .stack_size(SEARCH_THREAD_STACK_SIZE)
+                .spawn(move || {
+                    if options.mcts {
+                        let player = MCTS::deterministic(options.limits, options.seed);
+                        play_games(player, &options, &next_game)
+                    } else {
+                        let mut player = Searcher::deterministic(options.limits);
+                        let sizes = [
+                            ("hash", options.hash_size),
+                            ("evalhash", options.eval_hash_size),
+                        ];
+                        for (name, size) in sizes.iter() {
+                            player
+                                .set_option(name, &size.to_string())
+                                .unwrap_or_else(|e| panic!("Could not set {}: {}", name, e));
+                        }
+                        play_games(player, &options, &next_game)
+                    }
+                })
+                .expect("Could not start selfplay thread")
+        })
+        .collect();
+    let mut games: Vec<(usize, Vec<Position>)> = handles
+        .into_iter()
+        .flat_map(|handle| handle.join().expect("Selfplay thread panicked"))
+        .collect();
+    games.sort_by_key(|(game, _)| *game);
+    games
+        .into_iter()
+        .flat_map(|(_, positions)| positions)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{selfplay, SelfplayOptions};
+    use player::searchlimits::SearchLimits;
+
+    #[test]
+    fn selfplay_is_reproducible() {
+        let play = |threads: usize, seed: u64| {
+            let options = SelfplayOptions {
+                games: 3,
+                threads,
+                limits: SearchLimits::none().with_depth(1),
+                seed,
+                ..SelfplayOptions::default()
+            };
+            selfplay(options)
+                .into_iter()
+                .map(|position| (position.state.to_fen(), position.result, position.score))
+                .collect::<Vec<_>>()
+        };
+        let games = play(2, 0);
+        assert!(!games.is_empty());
+        assert_eq!(play(1, 0), games);
+        assert_ne!(play(2, 1), games);
+    }
+}
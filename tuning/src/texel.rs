@@ -0,0 +1,243 @@
+use crate::{sigmoid, Dataset, LabelledGameState, K};
+use player::evaluation::{EvaluationParameters, DEFAULT_ARRAY, NUM_PARAMETERS};
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::thread;
+
+pub struct TexelOptions {
+    pub iterations: usize,
+    pub learning_rate: f64,
+    /// Share of the states that is only used to measure the error
+    pub validation_share: f64,
+    pub threads: usize,
+}
+
+impl Default for TexelOptions {
+    fn default() -> Self {
+        TexelOptions {
+            iterations: 2000,
+            learning_rate: 0.05,
+            validation_share: 0.1,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+/// Sum of the squared errors of `states`
+fn error(states: &[&LabelledGameState], params: &EvaluationParameters) -> f64 {
+    states
+        .iter()
+        .map(|lgs| (sigmoid(K, lgs.2.score_with_parameters(params)) - lgs.1).powi(2))
+        .sum()
+}
+
+/// Sum of the squared errors of `states` and of their gradient by every parameter
+fn error_and_gradient(
+    states: &[&LabelledGameState],
+    params: &EvaluationParameters,
+) -> (f64, [f64; NUM_PARAMETERS]) {
+    let mut error = 0.;
+    let mut gradient = [0.; NUM_PARAMETERS];
+    for lgs in states.iter() {
+        let prediction = sigmoid(K, lgs.2.score_with_parameters(params));
+        error += (prediction - lgs.1).powi(2);
+        // derivative of the squared error by the score
+        let slope =
+            2. * (prediction - lgs.1) * prediction * (1. - prediction) * K * 10f64.ln() / 400.;
+        for (g, derivative) in gradient.iter_mut().zip(lgs.2.gradient(params).iter()) {
+            *g += slope * derivative;
+        }
+    }
+    (error, gradient)
+}
+
+/// Runs `f` on about equally large chunks of `states` on `threads` threads
+fn map_chunks<T: Send>(
+    states: &[&LabelledGameState],
+    threads: usize,
+    f: impl Fn(&[&LabelledGameState]) -> T + Sync,
+) -> Vec<T> {
+    let threads = threads.max(1);
+    let chunk_size = states.len().div_ceil(threads).max(1);
+    let f = &f;
+    thread::scope(|scope| {
+        let handles: Vec<_> = states
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || f(chunk)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Tuning thread panicked"))
+            .collect()
+    })
+}
+
+/// Mean squared error, computed on `threads` threads
+fn mean_error(states: &[&LabelledGameState], params: &EvaluationParameters, threads: usize) -> f64 {
+    let error: f64 = map_chunks(states, threads, |chunk| error(chunk, params))
+        .iter()
+        .sum();
+    error / states.len().max(1) as f64
+}
+
+/// Mean squared error and its gradient, computed on `threads` threads
+fn mean_error_and_gradient(
+    states: &[&LabelledGameState],
+    params: &EvaluationParameters,
+    threads: usize,
+) -> (f64, [f64; NUM_PARAMETERS]) {
+    let (mut error, mut gradient) = (0., [0.; NUM_PARAMETERS]);
+    for (chunk_error, chunk_gradient) in
+        map_chunks(states, threads, |chunk| error_and_gradient(chunk, params))
+    {
+        error += chunk_error;
+        for (g, chunk_g) in gradient.iter_mut().zip(chunk_gradient.iter()) {
+            *g += chunk_g;
+        }
+    }
+    let len = states.len().max(1) as f64;
+    for g in gradient.iter_mut() {
+        *g /= len;
+    }
+    (error / len, gradient)
+}
+
+struct Adam {
+    m: [f64; NUM_PARAMETERS],
+    v: [f64; NUM_PARAMETERS],
+    t: i32,
+}
+
+impl Adam {
+    const BETA1: f64 = 0.9;
+    const BETA2: f64 = 0.999;
+    const EPSILON: f64 = 1e-8;
+
+    fn new() -> Adam {
+        Adam {
+            m: [0.; NUM_PARAMETERS],
+            v: [0.; NUM_PARAMETERS],
+            t: 0,
+        }
+    }
+
+    fn step(
+        &mut self,
+        params: &mut [f64; NUM_PARAMETERS],
+        gradient: &[f64; NUM_PARAMETERS],
+        learning_rate: f64,
+    ) {
+        self.t += 1;
+        let correction1 = 1. - Adam::BETA1.powi(self.t);
+        let correction2 = 1. - Adam::BETA2.powi(self.t);
+        for i in 0..NUM_PARAMETERS {
+            self.m[i] = Adam::BETA1 * self.m[i] + (1. - Adam::BETA1) * gradient[i];
+            self.v[i] = Adam::BETA2 * self.v[i] + (1. - Adam::BETA2) * gradient[i] * gradient[i];
+            params[i] -= learning_rate * (self.m[i] / correction1)
+                / ((self.v[i] / correction2).sqrt() + Adam::EPSILON);
+        }
+    }
+}
+
+/// Fits the evaluation parameters to the labels of `dataset` with Adam, starting from the
+/// default parameters. K stays at the value the player converts scores with, as it can not be
+/// told apart from the scale of the parameters. Returns the parameters with the lowest validation error
+pub fn tune(dataset: &Dataset, options: &TexelOptions) -> EvaluationParameters {
+    let mut states: Vec<&LabelledGameState> = dataset.0.iter().collect();
+    states.shuffle(&mut SmallRng::seed_from_u64(0));
+    let validation_len = (states.len() as f64 * options.validation_share) as usize;
+    let (validation, training) = states.split_at(validation_len);
+    println!(
+        "Tuning on {} states, validating on {}",
+        training.len(),
+        validation.len()
+    );
+
+    let mut params = DEFAULT_ARRAY;
+    let mut adam = Adam::new();
+    let mut best = (f64::INFINITY, params);
+    for iteration in 0..=options.iterations {
+        let evaluation_params = EvaluationParameters::from_array(params);
+        let (error, gradient) =
+            mean_error_and_gradient(training, &evaluation_params, options.threads);
+        // without a validation split the training error decides which parameters are the best
+        let validation_error = if validation.is_empty() {
+            error
+        } else {
+            mean_error(validation, &evaluation_params, options.threads)
+        };
+        if validation_error < best.0 {
+            best = (validation_error, params);
+        }
+        if iteration % 100 == 0 {
+            println!(
+                "Iteration {}, training error {}, validation error {}",
+                iteration, error, validation_error
+            );
+        }
+        if iteration < options.iterations {
+            adam.step(&mut params, &gradient, options.learning_rate);
+        }
+    }
+    println!("Best validation error {}", best.0);
+    EvaluationParameters::from_array(best.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{error, mean_error, mean_error_and_gradient};
+    use crate::LabelledGameState;
+    use game_sdk::gamerules::{calculate_legal_moves, is_game_finished};
+    use game_sdk::{ActionList, GameState};
+    use player::evaluation::{evaluate_trace, EvaluationParameters, DEFAULT, DEFAULT_ARRAY};
+
+    #[test]
+    fn gradient_matches_finite_differences() {
+        let mut state = GameState::new();
+        state.obstacles = (1u128 << 5) | (1u128 << 49) | (1u128 << 107);
+        let mut al = ActionList::default();
+        let mut states = Vec::new();
+        for ply in 0..40 {
+            calculate_legal_moves(&state, &mut al);
+            state.make_action(al[(ply * 7) % al.size]);
+            if is_game_finished(&state) {
+                break;
+            }
+            // a single piece on the board has no neighbours to stay connected to
+            if ply == 0 {
+                continue;
+            }
+            let label = [0., 0.5, 1.][ply % 3];
+            let trace = evaluate_trace(&state, &DEFAULT);
+            states.push(LabelledGameState(state.clone(), label, trace));
+        }
+        let states: Vec<&LabelledGameState> = states.iter().collect();
+        let mut params = DEFAULT_ARRAY;
+        for (i, param) in params.iter_mut().enumerate().skip(10) {
+            *param = i as f64 / 10. - 1.5;
+        }
+        let (mean, gradient) =
+            mean_error_and_gradient(&states, &EvaluationParameters::from_array(params), 3);
+        let single_thread = error(&states, &EvaluationParameters::from_array(params));
+        assert!((mean - single_thread / states.len() as f64).abs() < 1e-12);
+        // an empty set must not underflow the chunk size
+        assert_eq!(mean_error(&[], &DEFAULT, 0), 0.);
+        let epsilon = 1e-4;
+        for (i, derivative) in gradient.iter().enumerate() {
+            let (mut lower, mut upper) = (params, params);
+            lower[i] -= epsilon;
+            upper[i] += epsilon;
+            let difference = (mean_error(&states, &EvaluationParameters::from_array(upper), 1)
+                - mean_error(&states, &EvaluationParameters::from_array(lower), 1))
+                / (2. * epsilon);
+            assert!(
+                (difference - derivative).abs() < 1e-6,
+                "parameter {}: {} != {}",
+                i,
+                difference,
+                derivative
+            );
+        }
+    }
+}
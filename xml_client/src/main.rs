@@ -3,6 +3,8 @@ mod xml_client;
 mod xml_utils;
 
 use argparse::{ArgumentParser, Store};
+use player::cache::HASH_SIZE;
+use player::options::Configurable;
 // use player::mcts::MCTS as Player;
 use player::search::Searcher as Player;
 use xml_client::XMLClient;
@@ -11,6 +13,12 @@ fn main() {
     let mut host = "localhost".to_string();
     let mut port = "13050".to_string();
     let mut reservation = "".to_string();
+    let mut hash = HASH_SIZE;
+    let mut eval_hash = HASH_SIZE;
+    let mut learning_file = "".to_string();
+    let mut book_file = "".to_string();
+    let mut eval_file = "".to_string();
+    let mut eval_params = "".to_string();
     {
         let mut ap = ArgumentParser::new();
         ap.refer(&mut host)
@@ -22,15 +30,61 @@ fn main() {
             Store,
             "Reservation to join",
         );
+        ap.refer(&mut hash)
+            .add_option(&["--hash"], Store, "Size of the transposition table in MB");
+        ap.refer(&mut eval_hash).add_option(
+            &["--eval-hash"],
+            Store,
+            "Size of the evaluation cache in MB",
+        );
+        ap.refer(&mut learning_file).add_option(
+            &["--learning-file"],
+            Store,
+            "File that keeps deep search results across games",
+        );
+        ap.refer(&mut book_file)
+            .add_option(&["--book"], Store, "Opening book to play from");
+        ap.refer(&mut eval_file).add_option(
+            &["--eval-file"],
+            Store,
+            "Network that replaces the hand crafted evaluation",
+        );
+        ap.refer(&mut eval_params).add_option(
+            &["--eval-params"],
+            Store,
+            "File with name = value lines for the hand crafted evaluation",
+        );
         ap.parse_args_or_exit();
     }
     println!(
         "Parameters got are: {}:{} w/ reservation {}",
         host, port, reservation
     );
+    let mut player = Player::new();
+    for (name, value) in [("hash", hash), ("evalhash", eval_hash)].iter() {
+        if *value != HASH_SIZE {
+            if let Err(e) = player.set_option(name, &value.to_string()) {
+                println!("Could not set option {}: {}", name, e);
+            }
+        }
+    }
+    for (name, value) in [
+        ("learningfile", learning_file),
+        ("bookfile", book_file),
+        ("evalfile", eval_file),
+        ("evalparams", eval_params),
+    ]
+    .iter()
+    {
+        if !value.is_empty() {
+            if let Err(e) = player.set_option(name, value) {
+                println!("Could not set option {}: {}", name, e);
+            }
+        }
+    }
     let mut client = XMLClient::new();
     // Insert custom client listener here:
-    client.add_listener(Box::new(Player::new()));
+    client.add_listener(Box::new(player));
 
     client.run(&(host + ":" + port.as_str()), &reservation);
 }
@@ -140,6 +140,9 @@ impl XMLClient {
 
     fn handle_memento_node(&mut self, node: &mut XMLNode) {
         let state = node.as_memento();
+        if self.game_state.is_none() {
+            self.fire_listeners(&mut |listener| listener.on_game_start(&state));
+        }
         self.fire_listeners(&mut |listener| listener.on_update_state(&state));
         self.game_state = Some(state);
     }